@@ -0,0 +1,65 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rust_extension_fields::loadtest::{self, LoadConfig};
+
+/// Drive a running oracle server with concurrent `resolve_foreign_call` requests and report
+/// throughput, latency percentiles and error rates.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// URL of the oracle server.
+    #[arg(long, default_value = "http://127.0.0.1:3000")]
+    url: String,
+    /// JSONL file of requests, or of calls recorded with `serve --record`. Defaults to a
+    /// generated corpus of `get_pairing_witnesses` and `third_root` requests.
+    #[arg(long, value_name = "FILE")]
+    corpus: Option<PathBuf>,
+    /// Requests in the generated corpus.
+    #[arg(long, default_value_t = 16, conflicts_with = "corpus")]
+    corpus_size: usize,
+    /// Seed of the RNG the generated corpus is drawn from.
+    #[arg(long, default_value_t = 0, conflicts_with = "corpus")]
+    seed: u64,
+    /// Calls in flight at once.
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+    /// Calls sent in total, cycling through the corpus.
+    #[arg(long, default_value_t = 100)]
+    requests: usize,
+    /// Seconds a call may take before it counts as an error.
+    #[arg(long, default_value_t = 600)]
+    timeout_secs: u64,
+    /// Print the report as JSON, e.g. to compare runs.
+    #[arg(long)]
+    json: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let corpus = match &cli.corpus {
+        Some(path) => loadtest::load_corpus(path)?,
+        None => loadtest::generate_corpus(cli.seed, cli.corpus_size),
+    };
+    let config = LoadConfig {
+        concurrency: cli.concurrency,
+        requests: cli.requests,
+        timeout: Duration::from_secs(cli.timeout_secs),
+    };
+    let report = loadtest::run(&cli.url, &corpus, config).await?;
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cli() {
+    use clap::CommandFactory;
+    Cli::command().debug_assert();
+    assert!(Cli::try_parse_from(["oracle-load", "--corpus", "a", "--seed", "1"]).is_err());
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::registry::{OracleRegistry, ParamShape};
+
+/// Where the generated bindings of the default handlers live, relative to the repository root.
+pub const BINDINGS_PATH: &str = "lib/src/bn/oracles.nr";
+
+/// A Noir type an oracle takes or returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoirType {
+    Field,
+    Bool,
+    /// A BN254 `Fp12`, returned by oracles as its `[[Field; 3]; 12]` coefficient limbs.
+    Fp12,
+    Tuple(Vec<NoirType>),
+}
+
+impl NoirType {
+    /// How the type is spelled in Noir.
+    pub fn name(&self) -> String {
+        match self {
+            NoirType::Field => "Field".to_string(),
+            NoirType::Bool => "bool".to_string(),
+            NoirType::Fp12 => "Fp12".to_string(),
+            NoirType::Tuple(types) => tuple(types.iter().map(NoirType::name)),
+        }
+    }
+
+    /// The type the `#[oracle]` function returns in place of this one.
+    fn raw_name(&self) -> String {
+        match self {
+            NoirType::Fp12 => "[[Field; 3]; 12]".to_string(),
+            NoirType::Tuple(types) => tuple(types.iter().map(NoirType::raw_name)),
+            _ => self.name(),
+        }
+    }
+
+    // the expression turning the raw value `raw` into this type
+    fn convert_raw(&self, raw: &str) -> String {
+        match self {
+            NoirType::Fp12 => format!("Fp12::from_coeff({})", raw),
+            _ => raw.to_string(),
+        }
+    }
+
+    fn imports(&self, imports: &mut BTreeSet<&'static str>) {
+        match self {
+            NoirType::Fp12 => {
+                imports.insert("crate::bn::fp12::Fp12");
+            }
+            NoirType::Tuple(types) => types.iter().for_each(|ty| ty.imports(imports)),
+            _ => {}
+        }
+    }
+
+    /// The foreign call params nargo sends for an argument of this type.
+    pub fn input_shape(&self) -> Vec<ParamShape> {
+        match self {
+            NoirType::Field | NoirType::Bool => vec![ParamShape::Single],
+            NoirType::Fp12 => vec![ParamShape::Array(3); 12],
+            NoirType::Tuple(types) => types.iter().flat_map(NoirType::input_shape).collect(),
+        }
+    }
+
+    /// The foreign call values nargo expects back for a return value of this type.
+    pub fn output_shape(&self) -> Vec<ParamShape> {
+        match self {
+            NoirType::Field | NoirType::Bool => vec![ParamShape::Single],
+            NoirType::Fp12 => vec![ParamShape::Array(36)],
+            NoirType::Tuple(types) => types.iter().flat_map(NoirType::output_shape).collect(),
+        }
+    }
+}
+
+fn tuple(names: impl Iterator<Item = String>) -> String {
+    format!("({})", names.collect::<Vec<_>>().join(", "))
+}
+
+/// The Noir side of an oracle: its arguments and what the wrapper returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoirSignature {
+    pub params: Vec<(&'static str, NoirType)>,
+    pub output: NoirType,
+}
+
+impl NoirSignature {
+    pub fn input_shape(&self) -> Vec<ParamShape> {
+        self.params
+            .iter()
+            .flat_map(|(_, ty)| ty.input_shape())
+            .collect()
+    }
+}
+
+/// A Noir module declaring every oracle of `registry` that has a [`NoirSignature`]: the empty
+/// `#[oracle(name)]` function `__name_oracle`, and an unconstrained `__name` wrapper converting
+/// its raw answer to the declared types.
+pub fn generate(registry: &OracleRegistry) -> String {
+    let mut oracles = vec![];
+    let mut imports = BTreeSet::new();
+    for info in registry.list() {
+        let oracle = registry
+            .get(&info.name)
+            .expect("listed oracles are registered");
+        if let Some(signature) = oracle.noir_signature() {
+            signature
+                .params
+                .iter()
+                .for_each(|(_, ty)| ty.imports(&mut imports));
+            signature.output.imports(&mut imports);
+            oracles.push((info.name, signature));
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(
+        "// Generated by `rust_extension_fields bindings`, do not edit. Oracles are added to\n",
+    );
+    out.push_str("// the handlers of the oracle crate instead.\n");
+    for import in &imports {
+        let _ = writeln!(out, "use {};", import);
+    }
+    for (name, signature) in &oracles {
+        let params: Vec<String> = signature
+            .params
+            .iter()
+            .map(|(param, ty)| format!("{}: {}", param, ty.name()))
+            .collect();
+        let params = params.join(", ");
+        let args: Vec<&str> = signature.params.iter().map(|(param, _)| *param).collect();
+        let call = format!("__{}_oracle({})", name, args.join(", "));
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "#[oracle({})]", name);
+        let _ = writeln!(
+            out,
+            "pub unconstrained fn __{}_oracle({}) -> {} {{}}",
+            name,
+            params,
+            signature.output.raw_name()
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "pub unconstrained fn __{}({}) -> {} {{",
+            name,
+            params,
+            signature.output.name()
+        );
+        match &signature.output {
+            NoirType::Tuple(types) => {
+                let raw: Vec<String> = (0..types.len()).map(|i| format!("out{}", i)).collect();
+                let _ = writeln!(out, "    let ({}) = {};", raw.join(", "), call);
+                let values = types.iter().zip(&raw).map(|(ty, raw)| ty.convert_raw(raw));
+                let _ = writeln!(out, "    {}", tuple(values));
+            }
+            NoirType::Fp12 => {
+                let _ = writeln!(out, "    let out = {};", call);
+                let _ = writeln!(out, "    {}", signature.output.convert_raw("out"));
+            }
+            _ => {
+                let _ = writeln!(out, "    {}", call);
+            }
+        }
+        let _ = writeln!(out, "}}");
+    }
+    out
+}
+
+#[test]
+fn test_signatures_match_declared_shapes() {
+    let registry = OracleRegistry::with_default_handlers();
+    for info in registry.list() {
+        let oracle = registry.get(&info.name).unwrap();
+        if let Some(signature) = oracle.noir_signature() {
+            assert_eq!(signature.input_shape(), info.inputs, "{}", info.name);
+            assert_eq!(
+                signature.output.output_shape(),
+                info.outputs,
+                "{}",
+                info.name
+            );
+        }
+    }
+}
+
+// the checked in bindings have to be regenerated whenever a handler changes
+#[test]
+fn test_bindings_are_up_to_date() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join(BINDINGS_PATH);
+    let generated = generate(&OracleRegistry::with_default_handlers());
+    assert_eq!(
+        std::fs::read_to_string(path).unwrap(),
+        generated,
+        "run `cargo run -- bindings --output ../{}`",
+        BINDINGS_PATH
+    );
+    assert!(generated.contains(
+        "pub unconstrained fn __get_pairing_witnesses(a: Fp12) -> (Fp12, Fp12) {\n    \
+         let (out0, out1) = __get_pairing_witnesses_oracle(a);\n    \
+         (Fp12::from_coeff(out0), Fp12::from_coeff(out1))\n}\n"
+    ));
+}
@@ -0,0 +1,468 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::foreign_call::ForeignCallParam;
+use crate::limbs::parse_hex;
+use crate::ops::WITNESS_ALGORITHM_VERSION;
+
+/// Size limits and location of an [`OracleCache`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Entries kept in memory, least recently used ones are evicted first.
+    pub memory_entries: usize,
+    /// Directory of the on-disk store, `None` keeps the cache in memory only.
+    pub disk_dir: Option<PathBuf>,
+    /// Entries kept on disk, the oldest files are evicted first.
+    pub disk_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            memory_entries: 1024,
+            disk_dir: None,
+            disk_entries: 100_000,
+        }
+    }
+}
+
+/// Content addressed memoization of oracle answers, keyed by namespace (see
+/// [`crate::packages::PackageSettings::cache_namespace`]), strictness, function name, the
+/// normalized inputs and [`WITNESS_ALGORITHM_VERSION`]. Strict and lenient answers are kept
+/// apart, as a lenient handler answers inputs a strict one rejects.
+///
+/// The on-disk store keeps one `v<N>` directory per algorithm version, so bumping the version
+/// invalidates every entry computed by the old algorithm. The old directory is removed the next
+/// time a cache is opened on it, but only if it holds the [`CACHE_MARKER`] file, so a
+/// `--cache-dir` pointed at a directory with other `v*` entries leaves them alone.
+pub struct OracleCache {
+    config: CacheConfig,
+    memory: Mutex<Lru>,
+    disk: Mutex<DiskIndex>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl OracleCache {
+    pub fn open(config: CacheConfig) -> io::Result<Self> {
+        let mut disk = DiskIndex::default();
+        if let Some(dir) = &config.disk_dir {
+            let version_dir = dir.join(version_dir_name());
+            create_version_dir(&version_dir)?;
+            // entries of other algorithm versions can never be hit again
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                let name = entry.file_name();
+                if path != version_dir
+                    && is_version_dir_name(&name.to_string_lossy())
+                    && path.join(CACHE_MARKER).is_file()
+                {
+                    fs::remove_dir_all(path)?;
+                }
+            }
+            disk = DiskIndex::scan(&version_dir)?;
+        }
+        Ok(OracleCache {
+            memory: Mutex::new(Lru::new(config.memory_entries)),
+            disk: Mutex::new(disk),
+            config,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    pub fn get(
+        &self,
+        namespace: &str,
+        strict: bool,
+        function: &str,
+        inputs: &[ForeignCallParam<String>],
+    ) -> Option<Value> {
+        let key = cache_key(namespace, strict, function, inputs);
+        let mut found = self.memory.lock().unwrap().get(&key);
+        if found.is_none() {
+            found = self.read_disk(&key);
+            if let Some(outputs) = &found {
+                self.memory.lock().unwrap().insert(key, outputs.clone());
+            }
+        }
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn insert(
+        &self,
+        namespace: &str,
+        strict: bool,
+        function: &str,
+        inputs: &[ForeignCallParam<String>],
+        outputs: &Value,
+    ) {
+        let key = cache_key(namespace, strict, function, inputs);
+        if let Err(err) = self.write_disk(&key, function, outputs) {
+            tracing::warn!("failed to persist cached {} answer: {}", function, err);
+        }
+        self.memory.lock().unwrap().insert(key, outputs.clone());
+    }
+
+    /// Drops every entry, in memory and on disk.
+    pub fn invalidate(&self) -> io::Result<()> {
+        self.memory.lock().unwrap().clear();
+        if let Some(dir) = self.version_dir() {
+            let mut disk = self.disk.lock().unwrap();
+            fs::remove_dir_all(&dir)?;
+            create_version_dir(&dir)?;
+            *disk = DiskIndex::default();
+        }
+        Ok(())
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn memory_len(&self) -> usize {
+        self.memory.lock().unwrap().len()
+    }
+
+    fn version_dir(&self) -> Option<PathBuf> {
+        self.config
+            .disk_dir
+            .as_ref()
+            .map(|dir| dir.join(version_dir_name()))
+    }
+
+    fn read_disk(&self, key: &str) -> Option<Value> {
+        let path = self.version_dir()?.join(format!("{}.json", key));
+        let entry: Value = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+        entry.get("outputs").cloned()
+    }
+
+    fn write_disk(&self, key: &str, function: &str, outputs: &Value) -> io::Result<()> {
+        let Some(dir) = self.version_dir() else {
+            return Ok(());
+        };
+        let entry = json!({ "function": function, "outputs": outputs });
+        // write then rename, so a concurrent reader never sees half an entry
+        let tmp = dir.join(format!("{}.tmp", key));
+        fs::write(&tmp, serde_json::to_vec(&entry)?)?;
+        fs::rename(&tmp, dir.join(format!("{}.json", key)))?;
+        self.evict_disk(&dir, key)
+    }
+
+    // only entries this cache wrote (or found on open) are ever evicted, never a `.tmp` file
+    // another writer is still filling in
+    fn evict_disk(&self, dir: &Path, key: &str) -> io::Result<()> {
+        let mut disk = self.disk.lock().unwrap();
+        disk.insert(key);
+        while disk.len() > self.config.disk_entries {
+            let Some(oldest) = disk.pop_oldest() else {
+                break;
+            };
+            match fs::remove_file(dir.join(format!("{}.json", oldest))) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name of the file marking a `v<N>` directory as one the cache created, and may delete.
+pub const CACHE_MARKER: &str = ".oracle-cache";
+
+fn version_dir_name() -> String {
+    format!("v{}", WITNESS_ALGORITHM_VERSION)
+}
+
+// `v` followed by the algorithm version, e.g. `v1`
+fn is_version_dir_name(name: &str) -> bool {
+    name.strip_prefix('v')
+        .is_some_and(|version| !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn create_version_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(CACHE_MARKER), b"")
+}
+
+fn cache_key(
+    namespace: &str,
+    strict: bool,
+    function: &str,
+    inputs: &[ForeignCallParam<String>],
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(WITNESS_ALGORITHM_VERSION.to_be_bytes());
+    hasher.update(namespace.as_bytes());
+    hasher.update([0u8, strict as u8]);
+    hasher.update(function.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(normalized_inputs(inputs));
+    to_hex(&hasher.finalize())
+}
+
+/// A short hash of `inputs` that is equal for equal field values however they are written, to
+/// tell calls apart in the logs.
+pub fn inputs_hash(inputs: &[ForeignCallParam<String>]) -> String {
+    let mut hash = to_hex(&Sha256::digest(normalized_inputs(inputs)));
+    hash.truncate(16);
+    hash
+}
+
+fn normalized_inputs(inputs: &[ForeignCallParam<String>]) -> Vec<u8> {
+    let normalized: Vec<ForeignCallParam<String>> = inputs
+        .iter()
+        .map(|input| match input {
+            ForeignCallParam::Single(value) => ForeignCallParam::Single(normalize_hex(value)),
+            ForeignCallParam::Array(values) => {
+                ForeignCallParam::Array(values.iter().map(|v| normalize_hex(v)).collect())
+            }
+        })
+        .collect();
+    // serializing a list of strings can't fail
+    serde_json::to_vec(&normalized).unwrap()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// the same field value can be sent as "0x00ab", "AB" or "ab", they all hit the same entry. A
+// value parse_hex rejects is kept as sent, the oracle fails on it before anything is cached
+fn normalize_hex(value: &str) -> String {
+    parse_hex(value).map_or_else(|_| value.to_string(), |value| value.to_str_radix(16))
+}
+
+// the keys of the entries in the on-disk store, oldest first, so inserting doesn't have to list
+// the whole directory to know whether something has to go
+#[derive(Default)]
+struct DiskIndex {
+    order: VecDeque<String>,
+    keys: HashSet<String>,
+}
+
+impl DiskIndex {
+    // the finished entries of `dir`, ordered by when they were written
+    fn scan(dir: &Path) -> io::Result<Self> {
+        let mut files = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(key) = path.file_stem() {
+                    let key = key.to_string_lossy().into_owned();
+                    files.push((entry.metadata()?.modified()?, key));
+                }
+            }
+        }
+        files.sort();
+        let mut index = DiskIndex::default();
+        for (_, key) in files {
+            index.insert(&key);
+        }
+        Ok(index)
+    }
+
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    fn insert(&mut self, key: &str) {
+        if self.keys.insert(key.to_string()) {
+            self.order.push_back(key.to_string());
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Option<String> {
+        let key = self.order.pop_front()?;
+        self.keys.remove(&key);
+        Some(key)
+    }
+}
+
+// a small least recently used map, `tick` orders the entries by last use
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (Value, u64)>,
+    order: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    fn get(&mut self, key: &str) -> Option<Value> {
+        self.tick += 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(last_used);
+        *last_used = self.tick;
+        self.order.insert(self.tick, key.to_string());
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: Value) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&last_used);
+        }
+        self.order.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+fn single(value: &str) -> Vec<ForeignCallParam<String>> {
+    vec![ForeignCallParam::Single(value.to_string())]
+}
+
+#[cfg(test)]
+fn temp_cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("oracle-cache-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_cache_normalizes_inputs() {
+    let cache = OracleCache::open(CacheConfig::default()).unwrap();
+    cache.insert("ns", false, "third_root", &single("0x00AB"), &json!(1));
+    assert_eq!(
+        cache.get("ns", false, "third_root", &single("ab")),
+        Some(json!(1))
+    );
+    assert_eq!(cache.get("ns", false, "is_third_root", &single("ab")), None);
+    assert_eq!(cache.get("ns", false, "third_root", &single("")), None);
+    assert_eq!(cache.get("other", false, "third_root", &single("ab")), None);
+    assert_eq!(cache.get("ns", true, "third_root", &single("ab")), None);
+    assert_eq!((cache.hits(), cache.misses()), (1, 4));
+    assert_eq!(inputs_hash(&single("0x00AB")), inputs_hash(&single("ab")));
+    assert_eq!(inputs_hash(&single("ab")).len(), 16);
+}
+
+#[test]
+fn test_cache_evicts_least_recently_used() {
+    let config = CacheConfig {
+        memory_entries: 2,
+        ..CacheConfig::default()
+    };
+    let cache = OracleCache::open(config).unwrap();
+    cache.insert("ns", false, "f", &single("1"), &json!(1));
+    cache.insert("ns", false, "f", &single("2"), &json!(2));
+    cache.get("ns", false, "f", &single("1"));
+    cache.insert("ns", false, "f", &single("3"), &json!(3));
+    assert_eq!(cache.memory_len(), 2);
+    assert_eq!(cache.get("ns", false, "f", &single("2")), None);
+    assert_eq!(cache.get("ns", false, "f", &single("1")), Some(json!(1)));
+}
+
+#[test]
+fn test_cache_survives_restart_and_invalidates() {
+    let dir = temp_cache_dir("restart");
+    let config = CacheConfig {
+        disk_dir: Some(dir.clone()),
+        ..CacheConfig::default()
+    };
+    OracleCache::open(config.clone())
+        .unwrap()
+        .insert("ns", false, "f", &single("1"), &json!(1));
+    // a stale version directory is dropped on open, directories the cache didn't create are not
+    fs::create_dir_all(dir.join("v0")).unwrap();
+    fs::write(dir.join("v0").join(CACHE_MARKER), b"").unwrap();
+    for other in ["vendor", "venv", "v2"] {
+        fs::create_dir_all(dir.join(other)).unwrap();
+    }
+
+    let reopened = OracleCache::open(config).unwrap();
+    assert!(!dir.join("v0").exists());
+    for other in ["vendor", "venv", "v2"] {
+        assert!(dir.join(other).is_dir(), "{} was removed", other);
+    }
+    assert_eq!(reopened.get("ns", false, "f", &single("1")), Some(json!(1)));
+    reopened.invalidate().unwrap();
+    assert_eq!(reopened.get("ns", false, "f", &single("1")), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cache_limits_disk_entries() {
+    let dir = temp_cache_dir("limit");
+    let config = CacheConfig {
+        memory_entries: 0,
+        disk_dir: Some(dir.clone()),
+        disk_entries: 2,
+    };
+    // another writer's entry in progress
+    let in_flight = dir.join(version_dir_name()).join("other.tmp");
+    fs::create_dir_all(dir.join(version_dir_name())).unwrap();
+    fs::write(&in_flight, b"{").unwrap();
+    let cache = OracleCache::open(config.clone()).unwrap();
+    for i in 0..4 {
+        cache.insert("ns", false, "f", &single(&i.to_string()), &json!(i));
+    }
+    let stored = |dir: &Path| {
+        fs::read_dir(dir.join(version_dir_name()))
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|ext| ext == "json")
+            })
+            .count()
+    };
+    assert_eq!(stored(&dir), 2);
+    assert!(in_flight.exists());
+    // the limit holds across restarts too
+    let reopened = OracleCache::open(config).unwrap();
+    reopened.insert("ns", false, "f", &single("4"), &json!(4));
+    assert_eq!(stored(&dir), 2);
+    assert_eq!(reopened.get("ns", false, "f", &single("4")), Some(json!(4)));
+    fs::remove_dir_all(dir).unwrap();
+}
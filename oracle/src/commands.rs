@@ -0,0 +1,210 @@
+use anyhow::{anyhow, bail};
+use ark_bn254::{Fq, Fq12};
+use ark_ff::{Field, PrimeField};
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use ark_std::{One, UniformRand};
+use num_bigint::BigUint;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::foreign_call::ForeignCallParam;
+use crate::handlers::{cast_fp12_to_noir_fp12, cast_to_biguint, get_fq12_from_callparam};
+use crate::limbs::parse_hex;
+use crate::ops::witness_generator::{WitnessGenerator, WitnessGeneratorTrait};
+use crate::registry::OracleRegistry;
+use crate::session::Session;
+
+// The offline subcommands of the oracle binary. They go through the same registry and limb
+// codecs as the server, so what they print is what a nargo run would receive.
+
+/// Parses an Fq value written as hex, the way [`parse_hex`] reads field values everywhere else
+/// (so `0x1f`, `0X1F` and `1f` are the same value).
+pub fn parse_fq(value: &str) -> anyhow::Result<Fq> {
+    let parsed = parse_hex(value)?;
+    if parsed >= Fq::MODULUS.into() {
+        bail!("{} is not below the bn254 base field modulus", value.trim());
+    }
+    Ok(Fq::from(parsed))
+}
+
+/// An Fp12 from its 12 Fq coefficients, in the order of the Noir `Fp12` (c0.c0.c0 first).
+pub fn parse_fq12(coefficients: &[String]) -> anyhow::Result<Fq12> {
+    if coefficients.len() != 12 {
+        bail!("an Fp12 has 12 coefficients, got {}", coefficients.len());
+    }
+    let coefficients = coefficients
+        .iter()
+        .map(|c| parse_fq(c))
+        .collect::<anyhow::Result<Vec<Fq>>>()?;
+    Ok(Fq12::from_base_prime_field_elems(&coefficients).expect("12 coefficients"))
+}
+
+/// Reads Fp12 coefficients from a file, separated by whitespace or commas.
+pub fn read_coefficients(path: &Path) -> anyhow::Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(contents
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// The 12 coefficients of `value` as `0x` prefixed hex.
+pub fn fq12_coefficients(value: &Fq12) -> Vec<String> {
+    value
+        .to_base_prime_field_elements()
+        .map(|c| format!("0x{}", BigUint::from(c).to_str_radix(16)))
+        .collect()
+}
+
+/// The pairing witnesses `(c, u)` of `f`, as the `get_pairing_witnesses` oracle answers them.
+pub fn witness(registry: &OracleRegistry, f: &Fq12) -> anyhow::Result<(Value, Fq12, Fq12)> {
+    let answer = call(registry, "get_pairing_witnesses", f)?;
+    match decode_fq12s(&answer)?.as_slice() {
+        [c, u] => Ok((answer, *c, *u)),
+        other => bail!("expected 2 Fp12 values, got {}", other.len()),
+    }
+}
+
+/// A cube root of `f`, or `None` when `f` isn't a cube.
+pub fn third_root(registry: &OracleRegistry, f: &Fq12) -> anyhow::Result<Option<(Value, Fq12)>> {
+    // the oracle rejects a non-cube as an error, ask first so that reads as `None`
+    if call(registry, "is_third_root", f)?["values"][0] != "1" {
+        return Ok(None);
+    }
+    let answer = call(registry, "third_root", f)?;
+    match decode_fq12s(&answer)?.as_slice() {
+        [root] => Ok(Some((answer, *root))),
+        other => bail!("expected 1 Fp12 value, got {}", other.len()),
+    }
+}
+
+/// The integer held by comma separated hex limbs, least significant limb first, as nargo sends
+/// a `BigNum`.
+pub fn decode_limbs(limbs: &str) -> anyhow::Result<BigUint> {
+    Ok(cast_to_biguint(limbs.split(',').collect())?)
+}
+
+/// Outcome of one `selftest` check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+}
+
+/// Sanity checks of the witness generation math, on inputs drawn from an RNG seeded by `seed`.
+pub fn selftest(registry: &OracleRegistry, seed: u64) -> Vec<Check> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let a = Fq12::rand(&mut rng);
+    let cube = WitnessGenerator::rand_third_root_from(&mut rng);
+    let x = Fq::rand(&mut rng);
+
+    let mut checks = vec![];
+    let mut check = |name, passed| checks.push(Check { name, passed });
+    check(
+        "a^((p^12-1)/3) is a cube root of unity",
+        WitnessGenerator::pow_p12_minus_one_div_3(&a).pow([3]) == Fq12::one(),
+    );
+    let w = WitnessGenerator::find_27th_root();
+    check(
+        "the 27th root of unity has order 27",
+        w.pow([9]) != Fq12::one() && w.pow([27]) == Fq12::one(),
+    );
+    check(
+        "tonelli-shanks finds a cube root",
+        WitnessGenerator::tonelli_shanks_third_root(cube).pow([3]) == cube,
+    );
+    let modulus: BigUint = Fq::MODULUS.into();
+    let inverse = WitnessGenerator::invert(&x.into(), &modulus)
+        .to_biguint()
+        .map(Fq::from);
+    check("modular inversion", inverse == x.inverse());
+    check(
+        "fp12 limbs round trip",
+        get_fq12_from_callparam(&fp12_inputs(&a)).ok() == Some(a),
+    );
+    check(
+        "third_root oracle answers a cube root",
+        matches!(third_root(registry, &cube), Ok(Some((_, root))) if root.pow([3]) == cube),
+    );
+    check(
+        "get_pairing_witnesses oracle answers a 27th root of unity as u",
+        matches!(witness(registry, &a), Ok((_, _, u)) if u.pow([27]) == Fq12::one()),
+    );
+    checks
+}
+
+fn call(registry: &OracleRegistry, function: &str, f: &Fq12) -> anyhow::Result<Value> {
+    let session = Session::new(0, "", "");
+    Ok(registry.call(function, &fp12_inputs(f), &session)?)
+}
+
+// an Fp12 as the 12 BigNum params nargo sends for it
+pub(crate) fn fp12_inputs(value: &Fq12) -> Vec<ForeignCallParam<String>> {
+    cast_fp12_to_noir_fp12(*value)
+        .chunks(3)
+        .map(|limbs| ForeignCallParam::Array(limbs.to_vec()))
+        .collect()
+}
+
+// the Fp12 values of an oracle answer, each a flat array of 36 limbs
+fn decode_fq12s(answer: &Value) -> anyhow::Result<Vec<Fq12>> {
+    let values = answer["values"]
+        .as_array()
+        .ok_or_else(|| anyhow!("oracle answer has no values"))?;
+    values
+        .iter()
+        .map(|value| {
+            let limbs: Vec<String> = serde_json::from_value(value.clone())?;
+            let inputs: Vec<ForeignCallParam<String>> = limbs
+                .chunks(3)
+                .map(|limbs| ForeignCallParam::Array(limbs.to_vec()))
+                .collect();
+            Ok(get_fq12_from_callparam(&inputs)?)
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_fq() {
+    assert_eq!(parse_fq("0x10").unwrap(), Fq::from(16u64));
+    assert_eq!(parse_fq(" 10 ").unwrap(), Fq::from(16u64));
+    assert_eq!(parse_fq("0X10").unwrap(), Fq::from(16u64));
+    assert!(parse_fq("0xzz").is_err());
+    assert!(parse_fq("-1").is_err());
+    let modulus: BigUint = Fq::MODULUS.into();
+    assert!(parse_fq(&modulus.to_str_radix(16)).is_err());
+}
+
+#[test]
+fn test_parse_fq12_keeps_coefficient_order() {
+    let coefficients: Vec<String> = (1..=12).map(|i| format!("{:x}", i)).collect();
+    let f = parse_fq12(&coefficients).unwrap();
+    assert_eq!(f.c0.c0.c0, Fq::from(1u64));
+    assert_eq!(f.c0.c0.c1, Fq::from(2u64));
+    assert_eq!(f.c1.c2.c1, Fq::from(12u64));
+    assert_eq!(fq12_coefficients(&f)[11], "0xc");
+    assert!(parse_fq12(&coefficients[1..]).is_err());
+}
+
+#[test]
+fn test_decode_limbs() {
+    assert_eq!(decode_limbs("0x1, 0x0, 0x0").unwrap(), BigUint::from(1u32));
+    assert_eq!(
+        decode_limbs("0,1").unwrap(),
+        BigUint::from(1u32) << 120usize
+    );
+    assert!(decode_limbs("xyz").is_err());
+}
+
+#[test]
+fn test_third_root_of_non_cube() {
+    let registry = OracleRegistry::with_default_handlers();
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut f = Fq12::rand(&mut rng);
+    while WitnessGenerator::is_third_root(&f) {
+        f = Fq12::rand(&mut rng);
+    }
+    assert!(third_root(&registry, &f).unwrap().is_none());
+}
@@ -0,0 +1,820 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::registry::{OracleRegistry, ParamShape};
+
+// Reads the `#[oracle(...)]` declarations of a Noir crate and works out the foreign call layout
+// nargo uses for them, so they can be checked against the Rust handlers without compiling any
+// Noir. Only the subset of Noir needed for that is understood: `use` trees, structs, type
+// aliases and function signatures.
+
+/// Where a declaration was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// An `#[oracle(name)]` function and the parameters nargo sends and expects back for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OracleDeclaration {
+    /// The name in the attribute, which is what the foreign call is resolved by.
+    pub name: String,
+    /// The Noir function carrying the attribute.
+    pub function: String,
+    pub location: Location,
+    pub inputs: Vec<ParamShape>,
+    pub outputs: Vec<ParamShape>,
+}
+
+/// A disagreement between the Noir declarations and a registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// Noir declares an oracle no handler resolves.
+    MissingHandler { declaration: OracleDeclaration },
+    /// The handler's declared layout differs from what nargo sends or expects back.
+    ShapeMismatch {
+        declaration: OracleDeclaration,
+        side: Side,
+        registered: Vec<ParamShape>,
+    },
+    /// A handler that no Noir declaration calls. Harmless, but likely dead.
+    Undeclared { name: String },
+    /// A declaration whose types couldn't be laid out.
+    Unresolved { location: Location, message: String },
+}
+
+/// Which direction of a foreign call a [`Finding::ShapeMismatch`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The inputs nargo sends to the oracle.
+    Oracle,
+    /// The outputs the handler sends back.
+    Handler,
+}
+
+impl Side {
+    /// The shapes `declaration` gives this side.
+    pub fn declared<'a>(&self, declaration: &'a OracleDeclaration) -> &'a [ParamShape] {
+        match self {
+            Side::Oracle => &declaration.inputs,
+            Side::Handler => &declaration.outputs,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Oracle => write!(f, "inputs"),
+            Side::Handler => write!(f, "outputs"),
+        }
+    }
+}
+
+impl Finding {
+    /// Whether the finding means foreign calls will fail or be misread.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Finding::Undeclared { .. })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::MissingHandler { declaration } => write!(
+                f,
+                "{}: no handler for oracle {} (declared by {})",
+                declaration.location, declaration.name, declaration.function
+            ),
+            Finding::ShapeMismatch {
+                declaration,
+                side,
+                registered,
+            } => write!(
+                f,
+                "{}: {} {} are [{}] in Noir but [{}] in the handler",
+                declaration.location,
+                declaration.name,
+                side,
+                join_shapes(side.declared(declaration)),
+                join_shapes(registered)
+            ),
+            Finding::Undeclared { name } => {
+                write!(f, "handler {} is not declared as an oracle in Noir", name)
+            }
+            Finding::Unresolved { location, message } => write!(f, "{}: {}", location, message),
+        }
+    }
+}
+
+fn join_shapes(shapes: &[ParamShape]) -> String {
+    let shapes: Vec<String> = shapes.iter().map(|shape| shape.to_string()).collect();
+    shapes.join(", ")
+}
+
+/// Compares the oracles declared by the Noir crate at `src` (its `src/` directory) with the
+/// handlers of `registry`.
+pub fn check(src: &Path, registry: &OracleRegistry) -> anyhow::Result<Vec<Finding>> {
+    let krate = NoirCrate::load(src)?;
+    Ok(compare(&krate, registry))
+}
+
+fn compare(krate: &NoirCrate, registry: &OracleRegistry) -> Vec<Finding> {
+    let (declarations, mut findings) = krate.oracle_declarations();
+    for declaration in &declarations {
+        let Some(oracle) = registry.get(&declaration.name) else {
+            findings.push(Finding::MissingHandler {
+                declaration: declaration.clone(),
+            });
+            continue;
+        };
+        let sides = [
+            (Side::Oracle, oracle.input_shape()),
+            (Side::Handler, oracle.output_shape()),
+        ];
+        for (side, registered) in sides {
+            if side.declared(declaration) != registered {
+                findings.push(Finding::ShapeMismatch {
+                    declaration: declaration.clone(),
+                    side,
+                    registered,
+                });
+            }
+        }
+    }
+    for info in registry.list() {
+        if !declarations.iter().any(|d| d.name == info.name) {
+            findings.push(Finding::Undeclared { name: info.name });
+        }
+    }
+    findings
+}
+
+/// The parsed modules of a Noir crate, keyed by module path (`["bn", "fp12"]` for
+/// `crate::bn::fp12`).
+#[derive(Default)]
+pub struct NoirCrate {
+    modules: BTreeMap<Vec<String>, Module>,
+}
+
+#[derive(Default)]
+struct Module {
+    file: PathBuf,
+    uses: BTreeMap<String, Vec<String>>,
+    structs: BTreeMap<String, Vec<TypeExpr>>,
+    aliases: BTreeMap<String, TypeExpr>,
+    oracles: Vec<RawOracle>,
+}
+
+struct RawOracle {
+    name: String,
+    function: String,
+    line: usize,
+    params: Vec<TypeExpr>,
+    ret: TypeExpr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TypeExpr {
+    Path(Vec<String>, Vec<GenericArg>),
+    Array(Box<TypeExpr>, Option<usize>),
+    Tuple(Vec<TypeExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GenericArg {
+    Number(usize),
+    Type(TypeExpr),
+}
+
+// how a value is flattened into field elements
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Layout {
+    Scalar,
+    Array(Box<Layout>, usize),
+    Fields(Vec<Layout>),
+}
+
+impl Layout {
+    fn len(&self) -> usize {
+        match self {
+            Layout::Scalar => 1,
+            Layout::Array(element, len) => element.len() * len,
+            Layout::Fields(fields) => fields.iter().map(Layout::len).sum(),
+        }
+    }
+
+    // a struct (or tuple) crosses the boundary field by field, an array as one flat param
+    fn shapes(&self, shapes: &mut Vec<ParamShape>) {
+        match self {
+            Layout::Scalar => shapes.push(ParamShape::Single),
+            Layout::Array(..) => shapes.push(ParamShape::Array(self.len())),
+            Layout::Fields(fields) => fields.iter().for_each(|field| field.shapes(shapes)),
+        }
+    }
+}
+
+const SCALARS: [&str; 12] = [
+    "Field", "bool", "u1", "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64",
+];
+
+impl NoirCrate {
+    /// Parses every `.nr` file under `src`.
+    pub fn load(src: &Path) -> anyhow::Result<Self> {
+        let mut krate = NoirCrate::default();
+        let mut dirs = vec![src.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "nr") {
+                    let source = std::fs::read_to_string(&path)?;
+                    let module_path = module_path(path.strip_prefix(src)?);
+                    krate.add_module(module_path, path, &source)?;
+                }
+            }
+        }
+        Ok(krate)
+    }
+
+    fn add_module(&mut self, path: Vec<String>, file: PathBuf, source: &str) -> anyhow::Result<()> {
+        let mut module = parse_module(source)
+            .map_err(|(line, err)| anyhow::anyhow!("{}:{}: {}", file.display(), line, err))?;
+        module.file = file;
+        self.modules.insert(path, module);
+        Ok(())
+    }
+
+    /// Every oracle declaration with its foreign call layout, and the declarations whose types
+    /// couldn't be resolved.
+    pub fn oracle_declarations(&self) -> (Vec<OracleDeclaration>, Vec<Finding>) {
+        let mut declarations = vec![];
+        let mut unresolved = vec![];
+        for (path, module) in &self.modules {
+            for oracle in &module.oracles {
+                let location = Location {
+                    file: module.file.clone(),
+                    line: oracle.line,
+                };
+                let layouts = |types: &[TypeExpr]| -> Result<Vec<ParamShape>, String> {
+                    let mut shapes = vec![];
+                    for ty in types {
+                        self.layout(path, ty, 0)?.shapes(&mut shapes);
+                    }
+                    Ok(shapes)
+                };
+                match (
+                    layouts(&oracle.params),
+                    layouts(std::slice::from_ref(&oracle.ret)),
+                ) {
+                    (Ok(inputs), Ok(outputs)) => declarations.push(OracleDeclaration {
+                        name: oracle.name.clone(),
+                        function: oracle.function.clone(),
+                        location,
+                        inputs,
+                        outputs,
+                    }),
+                    (Err(message), _) | (_, Err(message)) => {
+                        unresolved.push(Finding::Unresolved { location, message })
+                    }
+                }
+            }
+        }
+        (declarations, unresolved)
+    }
+
+    fn layout(&self, module: &[String], ty: &TypeExpr, depth: usize) -> Result<Layout, String> {
+        if depth > 32 {
+            return Err("type definitions nest too deep".to_string());
+        }
+        match ty {
+            TypeExpr::Array(element, Some(len)) => Ok(Layout::Array(
+                Box::new(self.layout(module, element, depth + 1)?),
+                *len,
+            )),
+            TypeExpr::Array(_, None) => Err("array length is not a number".to_string()),
+            TypeExpr::Tuple(types) => types
+                .iter()
+                .map(|ty| self.layout(module, ty, depth + 1))
+                .collect::<Result<_, _>>()
+                .map(Layout::Fields),
+            TypeExpr::Path(segments, generics) => {
+                if let [name] = segments.as_slice() {
+                    if SCALARS.contains(&name.as_str()) {
+                        return Ok(Layout::Scalar);
+                    }
+                }
+                let (item_module, name) = self.resolve(module, segments, depth)?;
+                self.item_layout(&item_module, &name, generics, depth)
+            }
+        }
+    }
+
+    // the module an item is defined in and its name there, following `use` declarations
+    fn resolve(
+        &self,
+        module: &[String],
+        segments: &[String],
+        depth: usize,
+    ) -> Result<(Vec<String>, String), String> {
+        if depth > 32 {
+            return Err(format!("can't resolve {}", segments.join("::")));
+        }
+        let (name, prefix) = segments.split_last().ok_or("empty path")?;
+        if prefix.is_empty() {
+            let current = self.modules.get(module);
+            let defined = current
+                .is_some_and(|m| m.structs.contains_key(name) || m.aliases.contains_key(name));
+            if defined {
+                return Ok((module.to_vec(), name.clone()));
+            }
+            return match current.and_then(|m| m.uses.get(name)) {
+                Some(path) => self.resolve(module, path, depth + 1),
+                None => Err(format!("can't resolve {}", name)),
+            };
+        }
+        let target = match prefix[0].as_str() {
+            "crate" => prefix[1..].to_vec(),
+            "super" => {
+                let mut parent = module[..module.len().saturating_sub(1)].to_vec();
+                parent.extend_from_slice(&prefix[1..]);
+                parent
+            }
+            "self" => [module, &prefix[1..]].concat(),
+            // another crate, none of which we have the sources of
+            _ => return Ok((prefix.to_vec(), name.clone())),
+        };
+        self.resolve(&target, std::slice::from_ref(name), depth + 1)
+    }
+
+    fn item_layout(
+        &self,
+        module: &[String],
+        name: &str,
+        generics: &[GenericArg],
+        depth: usize,
+    ) -> Result<Layout, String> {
+        // noir-bignum's `BigNum<N, MOD_BITS, Params>` is a struct of `limbs: [Field; N]`
+        if module == ["bignum"] && name == "BigNum" {
+            return match generics.first() {
+                Some(GenericArg::Number(limbs)) => Ok(Layout::Fields(vec![Layout::Array(
+                    Box::new(Layout::Scalar),
+                    *limbs,
+                )])),
+                _ => Err("BigNum without a literal limb count".to_string()),
+            };
+        }
+        let Some(defining) = self.modules.get(module) else {
+            return Err(format!("unknown type {}::{}", module.join("::"), name));
+        };
+        if let Some(fields) = defining.structs.get(name) {
+            return fields
+                .iter()
+                .map(|field| self.layout(module, field, depth + 1))
+                .collect::<Result<_, _>>()
+                .map(Layout::Fields);
+        }
+        match defining.aliases.get(name) {
+            Some(aliased) => self.layout(module, aliased, depth + 1),
+            None => Err(format!("unknown type {}", name)),
+        }
+    }
+}
+
+// `bn/mod.nr` and `bn.nr` are `crate::bn`, `bn/fp2.nr` is `crate::bn::fp2`
+fn module_path(relative: &Path) -> Vec<String> {
+    let mut path: Vec<String> = relative
+        .with_extension("")
+        .iter()
+        .map(|segment| segment.to_string_lossy().to_string())
+        .collect();
+    if matches!(
+        path.last().map(String::as_str),
+        Some("mod" | "lib" | "main")
+    ) {
+        path.pop();
+    }
+    path
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let (mut i, mut line) = (0, 1);
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            // block comments nest in Noir
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    line += usize::from(chars[i] == '\n');
+                    i += 1;
+                }
+            }
+        } else if c == '"' {
+            let start = line;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                line += usize::from(chars[i] == '\n');
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+            tokens.push(Token {
+                text: "\"\"".to_string(),
+                line: start,
+            });
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token {
+                text: chars[start..i].iter().collect(),
+                line,
+            });
+        } else {
+            let pair: String = [c, next.unwrap_or(' ')].iter().collect();
+            let text = if pair == "::" || pair == "->" {
+                pair
+            } else {
+                c.to_string()
+            };
+            i += text.len();
+            tokens.push(Token { text, line });
+        }
+    }
+    tokens
+}
+
+type ParseResult<T> = Result<T, (usize, String)>;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(self.pos + offset).map(|t| t.text.as_str())
+    }
+
+    fn line(&self) -> usize {
+        let last = self.tokens.last().map_or(1, |t| t.line);
+        self.tokens.get(self.pos).map_or(last, |t| t.line)
+    }
+
+    fn next(&mut self) -> ParseResult<String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or((self.line(), "unexpected end of file".to_string()))?;
+        self.pos += 1;
+        Ok(token.text.clone())
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let found = self.peek() == Some(text);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, text: &str) -> ParseResult<()> {
+        let line = self.line();
+        let found = self.next()?;
+        if found != text {
+            return Err((line, format!("expected `{}`, found `{}`", text, found)));
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self) -> ParseResult<String> {
+        let line = self.line();
+        let found = self.next()?;
+        if !found.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            return Err((line, format!("expected a name, found `{}`", found)));
+        }
+        Ok(found)
+    }
+
+    // skips a balanced `open ... close` group, the current token being `open`
+    fn skip_group(&mut self, open: &str, close: &str) -> ParseResult<()> {
+        let mut depth = 0;
+        loop {
+            let token = self.next()?;
+            if token == open {
+                depth += 1;
+            } else if token == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn ty(&mut self) -> ParseResult<TypeExpr> {
+        if self.eat("&") {
+            self.eat("mut");
+        }
+        if self.eat("[") {
+            let element = self.ty()?;
+            self.expect(";")?;
+            let len = self.next()?.parse().ok();
+            self.expect("]")?;
+            return Ok(TypeExpr::Array(Box::new(element), len));
+        }
+        if self.eat("(") {
+            let mut types = vec![];
+            while !self.eat(")") {
+                types.push(self.ty()?);
+                self.eat(",");
+            }
+            return Ok(TypeExpr::Tuple(types));
+        }
+        let mut segments = vec![self.ident()?];
+        while self.eat("::") {
+            segments.push(self.ident()?);
+        }
+        let mut generics = vec![];
+        if self.eat("<") {
+            while !self.eat(">") {
+                let arg = match self.peek().and_then(|t| t.parse().ok()) {
+                    Some(number) => {
+                        self.pos += 1;
+                        GenericArg::Number(number)
+                    }
+                    None => GenericArg::Type(self.ty()?),
+                };
+                generics.push(arg);
+                self.eat(",");
+            }
+        }
+        Ok(TypeExpr::Path(segments, generics))
+    }
+
+    fn use_tree(
+        &mut self,
+        prefix: Vec<String>,
+        uses: &mut BTreeMap<String, Vec<String>>,
+    ) -> ParseResult<()> {
+        if self.eat("{") {
+            while !self.eat("}") {
+                self.use_tree(prefix.clone(), uses)?;
+                self.eat(",");
+            }
+            return Ok(());
+        }
+        let mut path = prefix;
+        path.push(self.ident()?);
+        if self.eat("::") {
+            return self.use_tree(path, uses);
+        }
+        let alias = if self.eat("as") {
+            self.ident()?
+        } else {
+            path.last().cloned().unwrap_or_default()
+        };
+        uses.insert(alias, path);
+        Ok(())
+    }
+
+    fn struct_fields(&mut self) -> ParseResult<Vec<TypeExpr>> {
+        let mut fields = vec![];
+        if !self.eat("{") {
+            // a unit struct
+            self.eat(";");
+            return Ok(fields);
+        }
+        while !self.eat("}") {
+            if self.peek() == Some("#") {
+                self.pos += 1;
+                self.skip_group("[", "]")?;
+                continue;
+            }
+            if self.eat("pub") && self.peek() == Some("(") {
+                self.skip_group("(", ")")?;
+            }
+            self.ident()?;
+            self.expect(":")?;
+            fields.push(self.ty()?);
+            self.eat(",");
+        }
+        Ok(fields)
+    }
+
+    // the signature of the next `fn`, as (name, parameter types, return type)
+    fn signature(&mut self) -> ParseResult<(String, Vec<TypeExpr>, TypeExpr)> {
+        while self.next()? != "fn" {}
+        let name = self.ident()?;
+        if self.peek() == Some("<") {
+            self.skip_group("<", ">")?;
+        }
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.eat(")") {
+            self.eat("mut");
+            self.ident()?;
+            self.expect(":")?;
+            params.push(self.ty()?);
+            self.eat(",");
+        }
+        let ret = if self.eat("->") {
+            self.ty()?
+        } else {
+            TypeExpr::Tuple(vec![])
+        };
+        Ok((name, params, ret))
+    }
+}
+
+fn parse_module(source: &str) -> ParseResult<Module> {
+    let mut parser = Parser {
+        tokens: tokenize(source),
+        pos: 0,
+    };
+    let mut module = Module::default();
+    while let Some(token) = parser.peek() {
+        match token {
+            "use" => {
+                parser.pos += 1;
+                parser.use_tree(vec![], &mut module.uses)?;
+            }
+            "struct" => {
+                parser.pos += 1;
+                let name = parser.ident()?;
+                if parser.peek() == Some("<") {
+                    parser.skip_group("<", ">")?;
+                }
+                let fields = parser.struct_fields()?;
+                module.structs.insert(name, fields);
+            }
+            "type" => {
+                parser.pos += 1;
+                let name = parser.ident()?;
+                if parser.peek() == Some("<") {
+                    parser.skip_group("<", ">")?;
+                }
+                parser.expect("=")?;
+                module.aliases.insert(name, parser.ty()?);
+            }
+            "#" if parser.peek_at(1) == Some("[") && parser.peek_at(2) == Some("oracle") => {
+                let line = parser.line();
+                parser.pos += 3;
+                parser.expect("(")?;
+                let name = parser.ident()?;
+                parser.expect(")")?;
+                parser.expect("]")?;
+                let (function, params, ret) = parser.signature()?;
+                module.oracles.push(RawOracle {
+                    name,
+                    function,
+                    line,
+                    params,
+                    ret,
+                });
+            }
+            _ => parser.pos += 1,
+        }
+    }
+    Ok(module)
+}
+
+#[cfg(test)]
+fn test_crate(files: &[(&str, &str)]) -> NoirCrate {
+    let mut krate = NoirCrate::default();
+    for (path, source) in files {
+        let module = module_path(Path::new(path));
+        krate
+            .add_module(module, PathBuf::from(path), source)
+            .unwrap();
+    }
+    krate
+}
+
+#[test]
+fn test_layout_of_nested_structs_and_tuples() {
+    let krate = test_crate(&[
+        (
+            "fp.nr",
+            "use bignum::BigNum;\npub type Fp = BigNum<3, 254, Params>;\n\
+             pub struct Fp2 { pub(crate) c0: Fp, /* imaginary */ pub c1: Fp }",
+        ),
+        (
+            "oracles/mod.nr",
+            "use crate::fp::{Fp, Fp2};\n\n\
+             #[oracle(pair)]\n\
+             unconstrained fn __pair(a: Fp2, flag: bool) -> ([[Field; 3]; 2], Fp) {}\n",
+        ),
+    ]);
+    let (declarations, unresolved) = krate.oracle_declarations();
+    assert!(unresolved.is_empty());
+    assert_eq!(
+        declarations,
+        vec![OracleDeclaration {
+            name: "pair".to_string(),
+            function: "__pair".to_string(),
+            location: Location {
+                file: PathBuf::from("oracles/mod.nr"),
+                line: 3,
+            },
+            inputs: vec![
+                ParamShape::Array(3),
+                ParamShape::Array(3),
+                ParamShape::Single
+            ],
+            outputs: vec![ParamShape::Array(6), ParamShape::Array(3)],
+        }]
+    );
+}
+
+#[test]
+fn test_compare_reports_drift() {
+    let krate = test_crate(&[(
+        "lib.nr",
+        "#[oracle(witness_gen)] fn a() -> [Field; 2] {}\n\
+         #[oracle(nope)] fn b() {}\n\
+         #[oracle(third_root)] fn c(x: Missing) {}\n",
+    )]);
+    let findings = compare(&krate, &OracleRegistry::with_default_handlers());
+    let messages: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "lib.nr:3: can't resolve Missing",
+            "lib.nr:1: witness_gen outputs are [array(2)] in Noir but [single] in the handler",
+            "lib.nr:2: no handler for oracle nope (declared by b)",
+            "handler get_pairing_witnesses is not declared as an oracle in Noir",
+            "handler is_third_root is not declared as an oracle in Noir",
+            "handler random_third_root is not declared as an oracle in Noir",
+            "handler third_root is not declared as an oracle in Noir",
+        ]
+    );
+    assert!(matches!(
+        findings[1],
+        Finding::ShapeMismatch {
+            side: Side::Handler,
+            ..
+        }
+    ));
+    assert_eq!(findings.iter().filter(|f| f.is_error()).count(), 3);
+}
+
+// the drift check itself: the library in this repository against the default handlers
+#[test]
+fn test_lib_declarations_match_handlers() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("../lib/src");
+    let findings = check(&src, &OracleRegistry::with_default_handlers()).unwrap();
+    let errors: Vec<String> = findings
+        .iter()
+        .filter(|f| f.is_error())
+        .map(|f| f.to_string())
+        .collect();
+    assert!(errors.is_empty(), "{:#?}", errors);
+    assert_eq!(
+        findings,
+        vec![Finding::Undeclared {
+            name: "witness_gen".to_string()
+        }]
+    );
+}
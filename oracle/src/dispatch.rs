@@ -0,0 +1,790 @@
+use serde_json::Value;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+use crate::cache::{inputs_hash, OracleCache};
+use crate::error::OracleError;
+use crate::metrics::Metrics;
+use crate::packages::{PackageRouter, PackageSettings, Route};
+use crate::registry::{Oracle, OracleRegistry};
+use crate::replay::{RecordedCall, Recorder, ReplayLog};
+use crate::request::{RequestData, Requests};
+use crate::session::SessionStore;
+
+#[cfg(test)]
+use crate::foreign_call::ForeignCallParam;
+#[cfg(test)]
+use crate::request::test_request as request;
+#[cfg(test)]
+use serde_json::json;
+
+/// Bounds on the blocking computations a dispatcher runs at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerLimits {
+    /// Maximum number of blocking oracle calls computing at once, further calls wait for a slot.
+    pub max_in_flight: usize,
+    /// How long a call may take (waiting for a slot included) before it is answered with a
+    /// timeout error. `None` waits forever.
+    pub call_timeout: Option<Duration>,
+}
+
+impl Default for WorkerLimits {
+    fn default() -> Self {
+        WorkerLimits {
+            max_in_flight: std::thread::available_parallelism().map_or(4, |n| n.get()),
+            call_timeout: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+/// Resolves decoded `resolve_foreign_call` requests against the registry of oracles of the
+/// package they come from.
+#[derive(Clone)]
+pub struct Dispatcher {
+    router: Arc<PackageRouter>,
+    limits: WorkerLimits,
+    in_flight: Arc<Semaphore>,
+    sessions: Arc<SessionStore>,
+    recorder: Option<Arc<Recorder>>,
+    replay: Option<Arc<ReplayLog>>,
+    cache: Option<Arc<OracleCache>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Dispatcher {
+    pub fn new(registry: OracleRegistry) -> Self {
+        let limits = WorkerLimits::default();
+        Dispatcher {
+            router: Arc::new(PackageRouter::new(Route::new(registry))),
+            limits,
+            in_flight: Arc::new(Semaphore::new(limits.max_in_flight.max(1))),
+            sessions: Arc::new(SessionStore::default()),
+            recorder: None,
+            replay: None,
+            cache: None,
+            metrics: Arc::new(Metrics::new()),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: WorkerLimits) -> Self {
+        self.limits = limits;
+        self.in_flight = Arc::new(Semaphore::new(limits.max_in_flight.max(1)));
+        self
+    }
+
+    pub fn with_sessions(mut self, sessions: Arc<SessionStore>) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    /// Appends every successfully resolved call to `recorder`.
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Answers every call from `replay` instead of computing it.
+    pub fn with_replay(mut self, replay: Arc<ReplayLog>) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Serves repeated calls to cacheable oracles from `cache`.
+    pub fn with_cache(mut self, cache: Arc<OracleCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&Arc<OracleCache>> {
+        self.cache.as_ref()
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Serves every package with the route `router` picks for it.
+    pub fn with_router(mut self, router: Arc<PackageRouter>) -> Self {
+        self.router = router;
+        self
+    }
+
+    /// The registry of the calls no `[[package]]` rule matches.
+    pub fn registry(&self) -> &OracleRegistry {
+        &self.router.default_route().registry
+    }
+
+    pub fn router(&self) -> &Arc<PackageRouter> {
+        &self.router
+    }
+
+    pub fn sessions(&self) -> &Arc<SessionStore> {
+        &self.sessions
+    }
+
+    pub fn limits(&self) -> WorkerLimits {
+        self.limits
+    }
+
+    /// Number of blocking computations currently running.
+    pub fn in_flight(&self) -> usize {
+        self.limits.max_in_flight.max(1) - self.in_flight.available_permits()
+    }
+
+    pub fn resolve_request(&self, request: &RequestData) -> Result<Value, OracleError> {
+        let session = self.sessions.session_for(request);
+        self.router
+            .route(request)
+            .registry
+            .call(&request.function, &request.inputs, &session)
+    }
+
+    // a panic inside a handler is turned into a computation error so it can't take down the server
+    pub fn resolve_request_catching_panics(
+        &self,
+        request: &RequestData,
+    ) -> Result<Value, OracleError> {
+        let span = call_span(request);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let (result, source) = if let Some(replay) = &self.replay {
+            (
+                replay.lookup(&request.package_name, &request.function, &request.inputs),
+                "replay",
+            )
+        } else if let Some(outputs) = self.cached(request) {
+            (Ok(outputs), "cache")
+        } else {
+            let result = catching_panics(&request.function, || self.resolve_request(request));
+            self.store_cached(request, &result);
+            (result, "oracle")
+        };
+        self.finish(request, &result, source, started.elapsed());
+        result
+    }
+
+    /// Resolves `request` without blocking the calling (RPC) thread: blocking oracles run on the
+    /// worker pool once one of the `max_in_flight` slots is free, and the whole call is bounded
+    /// by the call timeout.
+    pub async fn resolve_request_async(&self, request: &RequestData) -> Result<Value, OracleError> {
+        async {
+            let started = Instant::now();
+            let (result, source) = if let Some(replay) = &self.replay {
+                (
+                    replay.lookup(&request.package_name, &request.function, &request.inputs),
+                    "replay",
+                )
+            } else if let Some(outputs) = self.cached(request) {
+                (Ok(outputs), "cache")
+            } else {
+                let result = match self.router.route(request).registry.get(&request.function) {
+                    Some(oracle) if oracle.is_blocking() => {
+                        self.resolve_blocking(oracle.clone(), request).await
+                    }
+                    _ => catching_panics(&request.function, || self.resolve_request(request)),
+                };
+                self.store_cached(request, &result);
+                (result, "oracle")
+            };
+            self.finish(request, &result, source, started.elapsed());
+            result
+        }
+        .instrument(call_span(request))
+        .await
+    }
+
+    async fn resolve_blocking(
+        &self,
+        oracle: Arc<dyn Oracle>,
+        request: &RequestData,
+    ) -> Result<Value, OracleError> {
+        let work = async {
+            let permit = self
+                .in_flight
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|err| OracleError::Computation(err.to_string()))?;
+            let request = request.clone();
+            let session = self.sessions.session_for(&request);
+            let span = Span::current();
+            // the permit moves into the worker so a timed out computation keeps its slot until
+            // it actually finishes
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let _entered = span.enter();
+                catching_panics(&request.function, || oracle.call(&request.inputs, &session))
+            })
+            .await
+            .map_err(|err| OracleError::Computation(err.to_string()))?
+        };
+        match self.limits.call_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, work)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(OracleError::Timeout(format!(
+                        "{} did not finish within {:?}",
+                        request.function, timeout
+                    )))
+                }),
+            None => work.await,
+        }
+    }
+
+    // the cache (if any) that may answer `request`, and the namespace of its package
+    fn cache_for(&self, request: &RequestData) -> Option<(&OracleCache, &PackageSettings)> {
+        let route = self.router.route(request);
+        let oracle = route.registry.get(&request.function)?;
+        let cache = self.cache.as_deref().filter(|_| oracle.is_cacheable())?;
+        Some((cache, &route.settings))
+    }
+
+    fn cached(&self, request: &RequestData) -> Option<Value> {
+        let (cache, settings) = self.cache_for(request)?;
+        cache.get(
+            settings.cache_namespace(),
+            settings.strict,
+            &request.function,
+            &request.inputs,
+        )
+    }
+
+    fn store_cached(&self, request: &RequestData, result: &Result<Value, OracleError>) {
+        if let (Some((cache, settings)), Ok(outputs)) = (self.cache_for(request), result) {
+            cache.insert(
+                settings.cache_namespace(),
+                settings.strict,
+                &request.function,
+                &request.inputs,
+                outputs,
+            );
+        }
+    }
+
+    // logs the outcome on the call's span and records it, `source` is where the answer came from
+    fn finish(
+        &self,
+        request: &RequestData,
+        result: &Result<Value, OracleError>,
+        source: &'static str,
+        took: Duration,
+    ) {
+        let span = Span::current();
+        span.record("source", source);
+        span.record("duration_us", took.as_micros() as u64);
+        match result {
+            Ok(_) => {
+                span.record("outcome", "ok");
+                tracing::info!("foreign call resolved");
+            }
+            Err(err) => {
+                span.record("outcome", "error");
+                tracing::warn!(code = err.code(), "foreign call failed: {}", err);
+            }
+        }
+        // unknown names are lumped together, a stray caller shouldn't blow up the label set
+        let function = match self.router.route(request).registry.get(&request.function) {
+            Some(oracle) => oracle.name(),
+            None => "unknown",
+        };
+        self.metrics
+            .observe(function, result.is_ok(), source == "cache", took);
+        // a replayed answer is already in a log
+        if source != "replay" {
+            self.record(request, result, took);
+        }
+    }
+
+    fn record(&self, request: &RequestData, result: &Result<Value, OracleError>, took: Duration) {
+        let (Some(recorder), Ok(outputs)) = (&self.recorder, result) else {
+            return;
+        };
+        let call = RecordedCall {
+            package_name: request.package_name.clone(),
+            function: request.function.clone(),
+            inputs: request.inputs.clone(),
+            outputs: outputs.clone(),
+            duration_ms: took.as_millis() as u64,
+        };
+        // a full disk shouldn't fail the nargo run, the answer itself is fine
+        if let Err(err) = recorder.record(&call) {
+            tracing::warn!("failed to record {} call: {}", request.function, err);
+        }
+    }
+
+    // dispatches every request of the batch in order. A single request (which is what nargo sends)
+    // gets its result (or error) back unchanged, a batch of several gets an array in the same order
+    // where every failed call is replaced by its own error entry
+    pub fn resolve_requests(&self, requests: &Requests) -> Result<Value, OracleError> {
+        let results: Vec<Result<Value, OracleError>> = requests
+            .0
+            .iter()
+            .map(|request| self.resolve_request_catching_panics(request))
+            .collect();
+        collect_results(results)
+    }
+
+    /// Async counterpart of [`Dispatcher::resolve_requests`], see [`Dispatcher::resolve_request_async`].
+    pub async fn resolve_requests_async(&self, requests: &Requests) -> Result<Value, OracleError> {
+        let mut results: Vec<Result<Value, OracleError>> = vec![];
+        for request in &requests.0 {
+            results.push(self.resolve_request_async(request).await);
+        }
+        collect_results(results)
+    }
+}
+
+// every foreign call runs in one of these, the steps fill in the empty fields as they go
+fn call_span(request: &RequestData) -> Span {
+    tracing::info_span!(
+        "foreign_call",
+        function = %request.function,
+        session_id = request.session_id,
+        package_name = %request.package_name,
+        input_hash = %inputs_hash(&request.inputs),
+        decode_us = Empty,
+        compute_us = Empty,
+        encode_us = Empty,
+        duration_us = Empty,
+        source = Empty,
+        outcome = Empty,
+    )
+}
+
+fn catching_panics<F>(function: &str, call: F) -> Result<Value, OracleError>
+where
+    F: FnOnce() -> Result<Value, OracleError>,
+{
+    panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|payload| {
+        let reason = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(OracleError::Computation(format!(
+            "{} panicked: {}",
+            function, reason
+        )))
+    })
+}
+
+fn collect_results(mut results: Vec<Result<Value, OracleError>>) -> Result<Value, OracleError> {
+    if results.len() == 1 {
+        return results.remove(0);
+    }
+    Ok(Value::Array(
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|err| err.to_json()))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+fn default_dispatcher() -> Dispatcher {
+    Dispatcher::new(OracleRegistry::with_default_handlers())
+}
+
+#[test]
+fn test_resolve_single_request_is_unwrapped() {
+    let dispatcher = default_dispatcher();
+    let requests = Requests(vec![request("witness_gen")]);
+    assert_eq!(
+        dispatcher.resolve_requests(&requests),
+        Ok(json!("Hello, world!"))
+    );
+
+    let requests = Requests(vec![request("not_an_oracle")]);
+    assert_eq!(
+        dispatcher.resolve_requests(&requests),
+        Err(OracleError::UnknownFunction("not_an_oracle".to_string()))
+    );
+}
+
+#[test]
+fn test_resolve_batch_in_order() {
+    let requests = Requests(vec![
+        request("witness_gen"),
+        request("not_an_oracle"),
+        request("witness_gen"),
+    ]);
+    let results = default_dispatcher().resolve_requests(&requests).unwrap();
+    assert_eq!(results[0], json!("Hello, world!"));
+    assert_eq!(
+        results[1]["error"]["code"],
+        json!(crate::error::UNKNOWN_FUNCTION_CODE)
+    );
+    assert_eq!(results[2], json!("Hello, world!"));
+}
+
+#[test]
+fn test_resolve_batch_reports_each_error() {
+    let requests = Requests(vec![request("third_root"), request("witness_gen")]);
+    let results = default_dispatcher().resolve_requests(&requests).unwrap();
+    assert_eq!(
+        results[0]["error"]["code"],
+        json!(crate::error::WRONG_ARITY_CODE)
+    );
+    assert_eq!(results[1], json!("Hello, world!"));
+}
+
+#[test]
+fn test_resolve_request_catches_panics() {
+    // the zero element has no inverse, which tonelli_shanks_third_root unwraps
+    let mut zero = request("get_pairing_witnesses");
+    zero.inputs = crate::commands::fp12_inputs(&ark_std::Zero::zero());
+    match default_dispatcher().resolve_request_catching_panics(&zero) {
+        Err(OracleError::Computation(msg)) => {
+            assert!(msg.starts_with("get_pairing_witnesses panicked"))
+        }
+        other => panic!("expected a computation error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_non_cube_third_root_releases_its_slot() {
+    use crate::ops::witness_generator::{WitnessGenerator, WitnessGeneratorTrait};
+    use ark_bn254::Fq12;
+    use ark_std::UniformRand;
+
+    let mut rng = ark_std::test_rng();
+    let mut non_cube = Fq12::rand(&mut rng);
+    while WitnessGenerator::is_third_root(&non_cube) {
+        non_cube = Fq12::rand(&mut rng);
+    }
+    let dispatcher = default_dispatcher().with_limits(WorkerLimits {
+        max_in_flight: 1,
+        call_timeout: Some(Duration::from_secs(60)),
+    });
+    let call = |value: &Fq12| RequestData {
+        inputs: crate::commands::fp12_inputs(value),
+        ..request("third_root")
+    };
+    // used to spin in Tonelli-Shanks forever, holding the only slot
+    match dispatcher.resolve_request_async(&call(&non_cube)).await {
+        Err(OracleError::Computation(msg)) => assert!(msg.contains("not a cube")),
+        other => panic!("expected a computation error, got {:?}", other),
+    }
+    assert_eq!(dispatcher.in_flight(), 0);
+    let cube = non_cube * non_cube * non_cube;
+    assert!(dispatcher.resolve_request_async(&call(&cube)).await.is_ok());
+}
+
+// a blocking oracle whose calls wait until the test opens the gate for them, so tests can hold
+// a worker slot for exactly as long as they need to
+#[cfg(test)]
+#[derive(Default)]
+struct GateState {
+    // calls currently computing, and the most there ever were at once
+    active: usize,
+    max_active: usize,
+    // calls allowed to finish that haven't yet
+    opened: usize,
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct Gate(Arc<(std::sync::Mutex<GateState>, std::sync::Condvar)>);
+
+#[cfg(test)]
+impl Gate {
+    // lets one waiting (or the next) call finish
+    fn open_one(&self) {
+        let (state, changed) = &*self.0;
+        state.lock().unwrap().opened += 1;
+        changed.notify_all();
+    }
+
+    fn state<T>(&self, read: impl FnOnce(&GateState) -> T) -> T {
+        read(&self.0 .0.lock().unwrap())
+    }
+
+    // waits (without blocking the runtime) until `active` calls are computing
+    async fn wait_active(&self, active: usize) {
+        while self.state(|state| state.active) != active {
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+#[cfg(test)]
+impl crate::registry::OracleHandler for Gate {
+    type Input = ();
+    type Output = ();
+
+    fn name(&self) -> &'static str {
+        "gate"
+    }
+
+    fn input_shape(&self) -> Vec<crate::registry::ParamShape> {
+        vec![]
+    }
+
+    fn output_shape(&self) -> Vec<crate::registry::ParamShape> {
+        vec![]
+    }
+
+    fn decode(&self, _inputs: &[ForeignCallParam<String>]) -> Result<(), OracleError> {
+        Ok(())
+    }
+
+    fn compute(&self, _input: (), _session: &crate::session::Session) -> Result<(), OracleError> {
+        let (state, changed) = &*self.0;
+        let mut state = state.lock().unwrap();
+        state.active += 1;
+        state.max_active = state.max_active.max(state.active);
+        let mut state = changed
+            .wait_while(state, |state| state.opened == 0)
+            .unwrap();
+        state.opened -= 1;
+        state.active -= 1;
+        Ok(())
+    }
+
+    fn encode(&self, _output: ()) -> Result<Value, OracleError> {
+        Ok(json!({ "values": [] }))
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+fn gate_dispatcher(
+    gate: &Gate,
+    max_in_flight: usize,
+    call_timeout: Option<Duration>,
+) -> Dispatcher {
+    let mut registry = OracleRegistry::new();
+    registry.register(gate.clone());
+    Dispatcher::new(registry).with_limits(WorkerLimits {
+        max_in_flight,
+        call_timeout,
+    })
+}
+
+#[tokio::test]
+async fn test_resolve_async_times_out() {
+    let gate = Gate::default();
+    let dispatcher = gate_dispatcher(&gate, 1, Some(Duration::from_millis(50)));
+    match dispatcher.resolve_request_async(&request("gate")).await {
+        Err(OracleError::Timeout(msg)) => assert!(msg.starts_with("gate")),
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    // the timed out computation keeps its slot until it actually finishes
+    assert_eq!(dispatcher.in_flight(), 1);
+    gate.open_one();
+}
+
+#[tokio::test]
+async fn test_resolve_async_limits_in_flight() {
+    let gate = Gate::default();
+    let dispatcher = gate_dispatcher(&gate, 1, None);
+    let first = request("gate");
+    let second = request("gate");
+    let (a, b, ()) = tokio::join!(
+        dispatcher.resolve_request_async(&first),
+        dispatcher.resolve_request_async(&second),
+        async {
+            // one call holds the only slot, the other one has to wait for it
+            gate.wait_active(1).await;
+            assert_eq!(dispatcher.in_flight(), 1);
+            gate.open_one();
+            gate.open_one();
+        }
+    );
+    assert!(a.is_ok());
+    assert!(b.is_ok());
+    assert_eq!(gate.state(|state| state.max_active), 1);
+}
+
+#[tokio::test]
+async fn test_resolve_async_runs_cheap_oracles_inline() {
+    let dispatcher = default_dispatcher();
+    assert_eq!(
+        dispatcher
+            .resolve_requests_async(&Requests(vec![request("witness_gen")]))
+            .await,
+        Ok(json!("Hello, world!"))
+    );
+    assert_eq!(dispatcher.in_flight(), 0);
+}
+
+#[test]
+fn test_resolve_tracks_sessions() {
+    let dispatcher = default_dispatcher();
+    let mut other = request("witness_gen");
+    other.session_id = 1;
+    let requests = Requests(vec![
+        request("witness_gen"),
+        other,
+        request("not_an_oracle"),
+    ]);
+    dispatcher.resolve_requests(&requests).unwrap();
+    assert_eq!(dispatcher.sessions().len(), 2);
+    assert_eq!(dispatcher.sessions().get(0).unwrap().calls(), 2);
+    assert_eq!(dispatcher.sessions().get(1).unwrap().calls(), 1);
+}
+
+#[tokio::test]
+async fn test_record_then_replay() {
+    let path = std::env::temp_dir().join(format!("oracle-dispatch-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let recording = default_dispatcher().with_recorder(Arc::new(Recorder::open(&path).unwrap()));
+    let requests = Requests(vec![request("witness_gen"), request("not_an_oracle")]);
+    recording.resolve_requests_async(&requests).await.unwrap();
+
+    let replaying = default_dispatcher().with_replay(Arc::new(ReplayLog::load(&path).unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    let results = replaying.resolve_requests_async(&requests).await.unwrap();
+    assert_eq!(results[0], json!("Hello, world!"));
+    // failed calls are not recorded, so replay can't answer them
+    assert_eq!(
+        results[1]["error"]["code"],
+        json!(crate::error::NOT_RECORDED_CODE)
+    );
+}
+
+#[test]
+fn test_cacheable_oracles_are_served_from_cache() {
+    use crate::cache::CacheConfig;
+    let cache = Arc::new(OracleCache::open(CacheConfig::default()).unwrap());
+    let dispatcher = default_dispatcher().with_cache(cache.clone());
+    let mut is_third_root = request("is_third_root");
+    is_third_root.inputs = crate::commands::fp12_inputs(&ark_std::One::one());
+    let requests = Requests(vec![
+        is_third_root.clone(),
+        is_third_root,
+        request("witness_gen"),
+    ]);
+    let results = dispatcher.resolve_requests(&requests).unwrap();
+    assert_eq!(results[0], json!({"values": ["1"]}));
+    assert_eq!(results[1], results[0]);
+    // witness_gen is not cacheable, so it never touches the cache
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+}
+
+#[test]
+fn test_packages_share_a_cache_by_namespace_and_strictness() {
+    use crate::cache::CacheConfig;
+    use crate::packages::RoutingConfig;
+    let config = RoutingConfig::from_toml(
+        "[[package]]\nname = \"lenient\"\n\n[[package]]\nname = \"other\"\n\n\
+         [[package]]\nname = \"strict\"\nstrict = true\n",
+    )
+    .unwrap();
+    let router =
+        PackageRouter::from_config(&config, &OracleRegistry::with_default_handlers()).unwrap();
+    let cache = Arc::new(OracleCache::open(CacheConfig::default()).unwrap());
+    let dispatcher = default_dispatcher()
+        .with_router(Arc::new(router))
+        .with_cache(cache.clone());
+    let mut inputs = crate::commands::fp12_inputs(&ark_std::One::one());
+    inputs[0] = crate::request::non_canonical_fq(1);
+    let call = |package_name: &str| RequestData {
+        package_name: package_name.to_string(),
+        inputs: inputs.clone(),
+        ..request("is_third_root")
+    };
+    assert_eq!(
+        dispatcher.resolve_request_catching_panics(&call("lenient")),
+        Ok(json!({"values": ["1"]}))
+    );
+    // the lenient answer is shared with the other lenient package, but not the strict one
+    assert_eq!(
+        dispatcher.resolve_request_catching_panics(&call("other")),
+        Ok(json!({"values": ["1"]}))
+    );
+    assert!(matches!(
+        dispatcher.resolve_request_catching_panics(&call("strict")),
+        Err(OracleError::NonCanonical(_))
+    ));
+    assert_eq!((cache.hits(), cache.misses()), (1, 2));
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct LogBuffer(pub(crate) Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_calls_are_traced() {
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::subscriber::with_default(subscriber, || {
+        let dispatcher = default_dispatcher();
+        dispatcher.resolve_requests(&Requests(vec![
+            request("witness_gen"),
+            request("not_an_oracle"),
+        ]))
+    })
+    .unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<Value> = logs
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    let resolved = &lines[0]["span"];
+    assert_eq!(resolved["function"], "witness_gen");
+    assert_eq!(resolved["outcome"], "ok");
+    assert_eq!(resolved["source"], "oracle");
+    assert!(resolved["compute_us"].is_u64());
+    assert_eq!(resolved["input_hash"].as_str().unwrap().len(), 16);
+    assert_eq!(lines[1]["level"], "WARN");
+    assert_eq!(lines[1]["span"]["outcome"], "error");
+    assert_eq!(
+        lines[1]["fields"]["code"],
+        crate::error::UNKNOWN_FUNCTION_CODE
+    );
+}
+
+#[test]
+fn test_requests_are_routed_by_package() {
+    use crate::packages::RoutingConfig;
+    let config = RoutingConfig::from_toml(
+        "[[package]]\nname = \"restricted\"\nhandlers = [\"witness_gen\"]\n",
+    )
+    .unwrap();
+    let router =
+        PackageRouter::from_config(&config, &OracleRegistry::with_default_handlers()).unwrap();
+    let dispatcher = default_dispatcher().with_router(Arc::new(router));
+    let restricted = |function| RequestData {
+        package_name: "restricted".to_string(),
+        ..request(function)
+    };
+    assert_eq!(
+        dispatcher.resolve_request(&restricted("witness_gen")),
+        Ok(json!("Hello, world!"))
+    );
+    assert_eq!(
+        dispatcher.resolve_request(&restricted("random_third_root")),
+        Err(OracleError::UnknownFunction(
+            "random_third_root".to_string()
+        ))
+    );
+    assert!(dispatcher
+        .resolve_request(&request("random_third_root"))
+        .is_ok());
+}
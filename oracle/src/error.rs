@@ -0,0 +1,138 @@
+use jsonrpsee::types::ErrorObjectOwned;
+use serde_json::{json, Value};
+use std::fmt;
+
+use crate::registry::ParamShape;
+
+// JSON-RPC reserves -32700 and -32600..-32603 for protocol level failures, which jsonrpsee
+// answers itself. Everything the oracle rejects gets its own code in the -32000..-32099
+// server range, so a client can tell a bad foreign call from a bad JSON-RPC message.
+pub const BAD_LIMB_CODE: i32 = -32001;
+pub const NON_CANONICAL_CODE: i32 = -32002;
+pub const COMPUTATION_CODE: i32 = -32003;
+pub const TIMEOUT_CODE: i32 = -32004;
+pub const NOT_RECORDED_CODE: i32 = -32005;
+pub const PARSE_ERROR_CODE: i32 = -32006;
+pub const UNKNOWN_FUNCTION_CODE: i32 = -32007;
+pub const WRONG_ARITY_CODE: i32 = -32008;
+pub const WRONG_SHAPE_CODE: i32 = -32009;
+
+/// Everything that can go wrong while resolving a single foreign call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OracleError {
+    /// The request payload is not valid JSON or does not have the `RequestData` layout.
+    Parse(String),
+    /// No oracle is registered under the requested function name.
+    UnknownFunction(String),
+    /// The oracle received a different number of inputs than it expects.
+    WrongArity { expected: usize, got: usize },
+    /// Input `index` has a different shape than the oracle declares for it.
+    WrongShape {
+        index: usize,
+        expected: ParamShape,
+        got: ParamShape,
+    },
+    /// A limb is not a valid hex encoded field value.
+    BadLimb(String),
+    /// A value decoded fine but lies outside of the range the oracle accepts.
+    NonCanonical(String),
+    /// The witness computation itself failed (or panicked).
+    Computation(String),
+    /// The call did not finish within the configured per-call timeout.
+    Timeout(String),
+    /// Replay mode only: the call is not in the replayed record log.
+    NotRecorded(String),
+}
+
+impl OracleError {
+    pub fn code(&self) -> i32 {
+        match self {
+            OracleError::Parse(_) => PARSE_ERROR_CODE,
+            OracleError::UnknownFunction(_) => UNKNOWN_FUNCTION_CODE,
+            OracleError::WrongArity { .. } => WRONG_ARITY_CODE,
+            OracleError::WrongShape { .. } => WRONG_SHAPE_CODE,
+            OracleError::BadLimb(_) => BAD_LIMB_CODE,
+            OracleError::NonCanonical(_) => NON_CANONICAL_CODE,
+            OracleError::Computation(_) => COMPUTATION_CODE,
+            OracleError::Timeout(_) => TIMEOUT_CODE,
+            OracleError::NotRecorded(_) => NOT_RECORDED_CODE,
+        }
+    }
+
+    // the error entry used for a failed call inside a batch response
+    pub fn to_json(&self) -> Value {
+        json!({ "error": { "code": self.code(), "message": self.to_string() } })
+    }
+}
+
+impl fmt::Display for OracleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OracleError::Parse(msg) => write!(f, "parse error: {}", msg),
+            OracleError::UnknownFunction(name) => write!(f, "unknown function: {}", name),
+            OracleError::WrongArity { expected, got } => {
+                write!(f, "wrong arity: expected {} inputs, got {}", expected, got)
+            }
+            OracleError::WrongShape {
+                index,
+                expected,
+                got,
+            } => write!(
+                f,
+                "wrong shape: expected input {} to be {}, got {}",
+                index, expected, got
+            ),
+            OracleError::BadLimb(msg) => write!(f, "bad limb: {}", msg),
+            OracleError::NonCanonical(msg) => write!(f, "non-canonical value: {}", msg),
+            OracleError::Computation(msg) => write!(f, "computation failed: {}", msg),
+            OracleError::Timeout(msg) => write!(f, "timed out: {}", msg),
+            OracleError::NotRecorded(msg) => write!(f, "not recorded: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for OracleError {}
+
+impl From<OracleError> for ErrorObjectOwned {
+    fn from(err: OracleError) -> Self {
+        ErrorObjectOwned::owned(err.code(), err.to_string(), None::<()>)
+    }
+}
+
+#[test]
+fn test_error_codes_are_distinct() {
+    let errors = [
+        OracleError::Parse(String::new()),
+        OracleError::UnknownFunction(String::new()),
+        OracleError::WrongArity {
+            expected: 12,
+            got: 0,
+        },
+        OracleError::WrongShape {
+            index: 0,
+            expected: ParamShape::Array(3),
+            got: ParamShape::Single,
+        },
+        OracleError::BadLimb(String::new()),
+        OracleError::NonCanonical(String::new()),
+        OracleError::Computation(String::new()),
+        OracleError::Timeout(String::new()),
+        OracleError::NotRecorded(String::new()),
+    ];
+    let mut codes: Vec<i32> = errors.iter().map(|e| e.code()).collect();
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes.len(), errors.len());
+    assert!(codes.iter().all(|code| (-32099..=-32000).contains(code)));
+}
+
+#[test]
+fn test_error_into_rpc_error_object() {
+    let err = OracleError::WrongArity {
+        expected: 12,
+        got: 3,
+    };
+    let obj: ErrorObjectOwned = err.into();
+    assert_eq!(obj.code(), WRONG_ARITY_CODE);
+    assert_eq!(obj.message(), "wrong arity: expected 12 inputs, got 3");
+}
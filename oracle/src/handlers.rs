@@ -1,85 +1,339 @@
-use ark_bn254::Fr;
 use ark_bn254::{Fq, Fq12, Fq2, Fq6};
-use ark_ff::Zero;
-use jsonrpsee::core::params;
-use num_bigint::BigInt;
 use num_bigint::BigUint;
-use num_traits::Num;
 use serde_json::{json, Value};
 // use std::str::FromStr;
 
+use crate::bindings::{NoirSignature, NoirType};
+use crate::error::OracleError;
 use crate::foreign_call::ForeignCallParam;
+use crate::limbs::{parse_hex, LimbCodec, LIMB_BITS};
 use crate::ops::witness_generator::WitnessGenerator;
 use crate::ops::witness_generator::WitnessGeneratorTrait;
+use crate::registry::{check_shape, OracleHandler, OracleRegistry, ParamShape};
+use crate::session::Session;
 
-// a struct that emulates the bignum Params params from the params.nr file
-struct Params {
-    has_multiplicative_inverse: bool,
-    modulus: BigUint,
-    double_modulus: Vec<Fr>,
-    redc_param: Vec<Fr>,
+// every Fp12 crosses the foreign call boundary as 12 BigNum<3, 254> coefficients
+pub const FP12_INPUT_SHAPE: [ParamShape; 12] = [ParamShape::Array(3); 12];
+// and comes back as a single [[Field; 3]; 12] value, which nargo receives flattened
+pub const FP12_OUTPUT_SHAPE: ParamShape = ParamShape::Array(36);
+
+pub struct WitnessGen;
+
+impl OracleHandler for WitnessGen {
+    type Input = ();
+    type Output = ();
+
+    fn name(&self) -> &'static str {
+        "witness_gen"
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        vec![]
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        vec![ParamShape::Single]
+    }
+
+    fn decode(&self, _inputs: &[ForeignCallParam<String>]) -> Result<(), OracleError> {
+        Ok(())
+    }
+
+    fn compute(&self, _input: (), _session: &Session) -> Result<(), OracleError> {
+        Ok(())
+    }
+
+    fn encode(&self, _output: ()) -> Result<Value, OracleError> {
+        Ok(Value::String("Hello, world!".to_string()))
+    }
+}
+
+#[derive(Default)]
+pub struct ThirdRoot {
+    /// Reject non-canonical inputs instead of reducing them with a warning.
+    pub strict: bool,
+}
+
+impl OracleHandler for ThirdRoot {
+    type Input = Fq12;
+    type Output = Fq12;
+
+    fn name(&self) -> &'static str {
+        "third_root"
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        FP12_INPUT_SHAPE.to_vec()
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        vec![FP12_OUTPUT_SHAPE]
+    }
+
+    fn noir_signature(&self) -> Option<NoirSignature> {
+        Some(NoirSignature {
+            params: vec![("a", NoirType::Fp12)],
+            output: NoirType::Fp12,
+        })
+    }
+
+    fn decode(&self, inputs: &[ForeignCallParam<String>]) -> Result<Fq12, OracleError> {
+        // the input has 12 elements, each a bignum representing an FP element
+        decode_fq12(inputs, self.strict)
+    }
+
+    fn compute(&self, input: Fq12, _session: &Session) -> Result<Fq12, OracleError> {
+        if !WitnessGenerator::is_third_root(&input) {
+            return Err(OracleError::Computation(
+                "third_root: input is not a cube".to_string(),
+            ));
+        }
+        Ok(WitnessGenerator::tonelli_shanks_third_root(input))
+    }
+
+    fn encode(&self, output: Fq12) -> Result<Value, OracleError> {
+        Ok(encode_fp12s(&[output]))
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
+
+    fn is_cacheable(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct IsThirdRoot {
+    /// Reject non-canonical inputs instead of reducing them with a warning.
+    pub strict: bool,
+}
+
+impl OracleHandler for IsThirdRoot {
+    type Input = Fq12;
+    type Output = bool;
+
+    fn name(&self) -> &'static str {
+        "is_third_root"
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        FP12_INPUT_SHAPE.to_vec()
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        vec![ParamShape::Single]
+    }
+
+    fn noir_signature(&self) -> Option<NoirSignature> {
+        Some(NoirSignature {
+            params: vec![("a", NoirType::Fp12)],
+            output: NoirType::Bool,
+        })
+    }
+
+    fn decode(&self, inputs: &[ForeignCallParam<String>]) -> Result<Fq12, OracleError> {
+        decode_fq12(inputs, self.strict)
+    }
+
+    fn compute(&self, input: Fq12, _session: &Session) -> Result<bool, OracleError> {
+        Ok(WitnessGenerator::is_third_root(&input))
+    }
+
+    fn encode(&self, output: bool) -> Result<Value, OracleError> {
+        // a single bool goes back as a flat list holding one hex value
+        let as_big_uint: BigUint = output.into();
+        let return_vec: Vec<String> = vec![as_big_uint.to_str_radix(16)];
+        Ok(json!({"values" : return_vec}))
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
+
+    fn is_cacheable(&self) -> bool {
+        true
+    }
 }
 
-/**** THERE'S A LOT OF BOILERPLATE INSIDE THESE "HANDLERS", THAT WE CAN PROBABLY PUT INTO COMMON HELPER FUNCTIONS ****/
+pub struct RandomThirdRoot;
 
-pub(crate) fn handle_witness_gen(inputs: &Vec<ForeignCallParam<String>>) -> Value {
-    Value::String("Hello, world!".to_string())
+impl OracleHandler for RandomThirdRoot {
+    type Input = ();
+    type Output = Fq12;
+
+    fn name(&self) -> &'static str {
+        "random_third_root"
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        vec![]
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        vec![FP12_OUTPUT_SHAPE]
+    }
+
+    fn noir_signature(&self) -> Option<NoirSignature> {
+        Some(NoirSignature {
+            params: vec![],
+            output: NoirType::Fp12,
+        })
+    }
+
+    fn decode(&self, _inputs: &[ForeignCallParam<String>]) -> Result<(), OracleError> {
+        Ok(())
+    }
+
+    fn compute(&self, _input: (), session: &Session) -> Result<Fq12, OracleError> {
+        // drawn from the session rng, so a rerun of the same session gets the same values
+        Ok(session.with_rng(WitnessGenerator::rand_third_root_from))
+    }
+
+    fn encode(&self, output: Fq12) -> Result<Value, OracleError> {
+        Ok(encode_fp12s(&[output]))
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
 }
 
-pub(crate) fn handle_third_root(inputs: &Vec<ForeignCallParam<String>>) -> Value {
-    // the input has 12 elements, each a bignum representing an FP element
-    // now we cast the bignums to bigUints
-    let fp12 = get_fq12_from_callparam(inputs);
-    let result = WitnessGenerator::tonelli_shanks_third_root(fp12);
-    let results_formatted = cast_fp12_to_noir_fp12(result);
-    let return_vec: Vec<Vec<String>> = vec![results_formatted];
-    let json_response = json!({"values" : return_vec});
-    json_response
+#[derive(Default)]
+pub struct GetPairingWitnesses {
+    /// Reject non-canonical inputs instead of reducing them with a warning.
+    pub strict: bool,
 }
 
-pub(crate) fn handle_is_third_root(inputs: &Vec<ForeignCallParam<String>>) -> Value {
-    let fp12 = get_fq12_from_callparam(inputs);
-    let result: bool = WitnessGenerator::is_third_root(&fp12);
+impl OracleHandler for GetPairingWitnesses {
+    type Input = Fq12;
+    type Output = (Fq12, Fq12);
+
+    fn name(&self) -> &'static str {
+        "get_pairing_witnesses"
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        FP12_INPUT_SHAPE.to_vec()
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        vec![FP12_OUTPUT_SHAPE; 2]
+    }
 
-    let as_big_uint: BigUint = result.into();
-    let as_hex_str = as_big_uint.to_str_radix(16);
-    let mut results: Vec<String> = vec![];
-    results.push(as_hex_str);
-    let oracle_return_data_the_noir_program_expects = results;
+    fn noir_signature(&self) -> Option<NoirSignature> {
+        Some(NoirSignature {
+            params: vec![("a", NoirType::Fp12)],
+            output: NoirType::Tuple(vec![NoirType::Fp12; 2]),
+        })
+    }
 
-    /**** FORMAT RESULT FOR NOIR CONSUMPTION, AND CONVERT RESULT TO JSON `Value` TYPE ****/
-    let return_vec = oracle_return_data_the_noir_program_expects; // Notice! This is a different type from the singular handle_get_sqrt function! Hence why the `Value` is being computed inside this function, instead in the calling function.
+    fn decode(&self, inputs: &[ForeignCallParam<String>]) -> Result<Fq12, OracleError> {
+        decode_fq12(inputs, self.strict)
+    }
+
+    fn compute(&self, input: Fq12, _session: &Session) -> Result<(Fq12, Fq12), OracleError> {
+        Ok(WitnessGenerator::witness_generator(input))
+    }
+
+    fn encode(&self, output: (Fq12, Fq12)) -> Result<Value, OracleError> {
+        let (c, u) = output;
+        Ok(encode_fp12s(&[c, u]))
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
 
-    let json_response = json!({"values" : return_vec});
-    json_response
+    fn is_cacheable(&self) -> bool {
+        true
+    }
 }
 
-pub(crate) fn handle_random_third_root(inputs: &Vec<ForeignCallParam<String>>) -> Value {
-    let result = WitnessGenerator::rand_third_root();
-    let results_formatted = cast_fp12_to_noir_fp12(result);
-    let return_vec: Vec<Vec<String>> = vec![results_formatted];
-    let json_response = json!({"values" : return_vec});
-    json_response
+/// Registers the oracles the Noir library in `lib/` declares.
+pub fn register_default_handlers(registry: &mut OracleRegistry) {
+    register_handlers(registry, false);
 }
 
-pub(crate) fn handle_get_pairing_witnesses(inputs: &Vec<ForeignCallParam<String>>) -> Value {
-    let fp12 = get_fq12_from_callparam(inputs);
-    let (c, u) = WitnessGenerator::witness_generator(fp12);
-    let c_formatted = cast_fp12_to_noir_fp12(c);
-    let u_formatted = cast_fp12_to_noir_fp12(u);
-    let return_vec: Vec<Vec<String>> = vec![c_formatted, u_formatted];
-    let json_response = json!({"values" : return_vec});
-    json_response
+/// Registers the oracles the Noir library in `lib/` declares, `strict` ones reject
+/// non-canonical inputs.
+pub fn register_handlers(registry: &mut OracleRegistry, strict: bool) {
+    registry.register(WitnessGen);
+    registry.register(ThirdRoot { strict });
+    registry.register(IsThirdRoot { strict });
+    registry.register(RandomThirdRoot);
+    registry.register(GetPairingWitnesses { strict });
 }
 
-pub fn get_fq12_from_callparam(inputs: &Vec<ForeignCallParam<String>>) -> Fq12 {
-    let mut biguints: Vec<Fq> = vec![];
-    for input in inputs {
-        let biguint = cast_to_biguint(callparam_to_string(input));
-        biguints.push(Fq::from(biguint));
+// an Fp12 input, when `strict` every coefficient has to be below p, otherwise the ones that
+// aren't are reduced with a warning
+fn decode_fq12(inputs: &[ForeignCallParam<String>], strict: bool) -> Result<Fq12, OracleError> {
+    let mut coefficients = decode_fq12_coefficients(inputs)?;
+    let codec = LimbCodec::bn254_fq();
+    for (index, coefficient) in coefficients.iter_mut().enumerate() {
+        if codec.is_canonical(coefficient) {
+            continue;
+        }
+        if strict {
+            return Err(OracleError::NonCanonical(format!(
+                "Fp12 coefficient {} ({}) is not below the bn254 base field modulus",
+                index,
+                fq12_coefficient_path(index)
+            )));
+        }
+        tracing::warn!(
+            coefficient = index,
+            path = fq12_coefficient_path(index),
+            "Fp12 coefficient {} ({}) is not below the bn254 base field modulus, reducing it",
+            index,
+            fq12_coefficient_path(index)
+        );
+        *coefficient %= codec.modulus();
     }
+    Ok(fq12_from_coefficients(coefficients))
+}
+
+// where the coefficient at `index` of the flattened Fp12 lives, e.g. `c0.c1.c1` for 3
+fn fq12_coefficient_path(index: usize) -> String {
+    format!("c{}.c{}.c{}", index / 6, (index % 6) / 2, index % 2)
+}
+
+// every Fp12 becomes one [[Field; 3]; 12] entry of the returned values
+fn encode_fp12s(outputs: &[Fq12]) -> Value {
+    let return_vec: Vec<Vec<String>> = outputs.iter().map(|f| cast_fp12_to_noir_fp12(*f)).collect();
+    json!({"values" : return_vec})
+}
+
+/// An Fp12 input, decoded leniently: coefficients that aren't below p are reduced with a warning.
+pub fn get_fq12_from_callparam(inputs: &[ForeignCallParam<String>]) -> Result<Fq12, OracleError> {
+    decode_fq12(inputs, false)
+}
+
+// the 12 BigNum<3, 254> coefficients of an Fp12 input, as sent (not reduced)
+fn decode_fq12_coefficients(
+    inputs: &[ForeignCallParam<String>],
+) -> Result<Vec<BigUint>, OracleError> {
+    check_shape(&FP12_INPUT_SHAPE, inputs)?;
+    let codec = LimbCodec::bn254_fq();
+    inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            codec.decode_param(input).map_err(|err| match err {
+                OracleError::BadLimb(message) => {
+                    OracleError::BadLimb(format!("input {}: {}", index, message))
+                }
+                err => err,
+            })
+        })
+        .collect()
+}
+
+fn fq12_from_coefficients(coefficients: Vec<BigUint>) -> Fq12 {
+    let biguints: Vec<Fq> = coefficients.into_iter().map(Fq::from).collect();
     // now cast these into an ark_ff::Fq12 element
-    let fp12 = Fq12 {
+    Fq12 {
         c0: Fq6 {
             c0: Fq2 {
                 c0: biguints[0],
@@ -108,8 +362,7 @@ pub fn get_fq12_from_callparam(inputs: &Vec<ForeignCallParam<String>>) -> Fq12 {
                 c1: biguints[11],
             },
         },
-    };
-    fp12
+    }
 }
 
 pub(crate) fn cast_fp12_to_noir_fp12(input: Fq12) -> Vec<String> {
@@ -129,141 +382,180 @@ pub(crate) fn cast_fp12_to_noir_fp12(input: Fq12) -> Vec<String> {
         input.c1.c2.c1.into(),
     ];
 
+    let codec = LimbCodec::bn254_fq();
     let mut results_formatted: Vec<String> = vec![];
     for result in limbs {
-        let limbs = cast_bigint_to_bignum_limbs(&result.into(), 3);
-        for limb in limbs {
-            results_formatted.push(limb);
-        }
+        let limbs = codec
+            .encode(&result)
+            .expect("reduced Fq elements fit in BigNum<3, 254>");
+        results_formatted.extend(limbs);
     }
     results_formatted
 }
 
-pub(crate) fn cast_to_biguint(input_strings: Vec<&str>) -> BigUint {
-    // split the limbs
-    let mut limbs: Vec<BigUint> = vec![];
-    for input_string in input_strings {
-        // handle the case of a zero input
-        if input_string == "" {
-            let x_big_uint = BigUint::from_str_radix("0", 16).unwrap();
-            limbs.push(x_big_uint);
-        } else {
-            let x_big_uint: BigUint = BigUint::from_str_radix(input_string, 16).unwrap();
-            limbs.push(x_big_uint);
-        }
-    }
-    // a constant 2^120 as biguint
-    let base: BigUint = BigUint::from(2u32);
-    let exp = 120u32;
-    let shift_constant = base.pow(exp);
-    let mut res = BigUint::ZERO;
-    for i in 0..limbs.len() {
-        res = res + &limbs[i] * &shift_constant.pow(i as u32);
+// the value of 120 bit limbs, least significant first, however many there are
+pub(crate) fn cast_to_biguint(input_strings: Vec<&str>) -> Result<BigUint, OracleError> {
+    if input_strings.is_empty() {
+        return Ok(BigUint::ZERO);
     }
-    res
-}
-
-// helper function to get limbs of a big num and pack them into a vector of Fr elements
-pub(crate) fn gets_limbs(input_strings: Vec<&str>) -> Vec<Fr> {
-    let mut limbs: Vec<Fr> = vec![];
-    for input_string in input_strings {
-        // handle the case of a zero input
-        if input_string == "" {
-            let x_big_uint = BigUint::from_str_radix("0", 16).unwrap();
-            let limb: Fr = x_big_uint.into();
-            limbs.push(limb);
-        } else {
-            let x_big_uint: BigUint = BigUint::from_str_radix(input_string, 16).unwrap();
-            let limb: Fr = x_big_uint.into();
-            limbs.push(limb);
-        }
-    }
-    limbs
+    LimbCodec::unbounded(LIMB_BITS, input_strings.len()).decode(&input_strings)
 }
 
 pub(crate) fn callparam_to_string(input: &ForeignCallParam<String>) -> Vec<&str> {
     match input {
-        ForeignCallParam::Single(value) => vec![value.trim_start_matches('0')],
-        ForeignCallParam::Array(values) => values
-            .into_iter()
-            .map(|v| v.trim_start_matches('0'))
-            .collect(),
+        ForeignCallParam::Single(value) => vec![value.as_str()],
+        ForeignCallParam::Array(values) => values.iter().map(String::as_str).collect(),
     }
 }
 
-pub(crate) fn get_u32_from_callparam(input: &ForeignCallParam<String>) -> u32 {
-    let input_string = callparam_to_string(input)[0];
-    u32::from_str_radix(input_string, 16).unwrap()
+pub(crate) fn get_u32_from_callparam(input: &ForeignCallParam<String>) -> Result<u32, OracleError> {
+    let input_string = *callparam_to_string(input)
+        .first()
+        .ok_or_else(|| OracleError::BadLimb("empty param".to_string()))?;
+    let value = parse_hex(input_string)?;
+    u32::try_from(&value)
+        .map_err(|_| OracleError::NonCanonical(format!("{:?} is not a u32", input_string)))
 }
 
-pub(crate) fn get_bool_from_callparam(input: &ForeignCallParam<String>) -> bool {
-    let mut input_string = callparam_to_string(input)[0];
-    if input_string == "" {
-        input_string = "0";
+pub(crate) fn get_bool_from_callparam(
+    input: &ForeignCallParam<String>,
+) -> Result<bool, OracleError> {
+    match get_u32_from_callparam(input)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        other => Err(OracleError::NonCanonical(format!(
+            "{} is not a bool",
+            other
+        ))),
     }
-    let res = u32::from_str_radix(input_string, 16).unwrap();
-    res == 1
 }
 
-pub(crate) fn cast_biguint_to_bignum_limbs(input: &BigUint, num_limbs: u32) -> Vec<String> {
-    // a constant 2^120 as biguint
-    let base: BigUint = BigUint::from(2u32);
-    let exp = 120u32;
-    let shift_constant = base.pow(exp);
-    let mut input_copy = input.clone();
-    // an empty array of size num_limbs of type hex limbs
-    let mut limbs_hex: Vec<String> = vec![];
-    for i in 0..num_limbs {
-        let remainder = &input_copy % &shift_constant;
-        limbs_hex.push(remainder.to_str_radix(16));
-        let quetient: BigUint = input_copy / &shift_constant;
-        input_copy = quetient.clone();
-    }
-    limbs_hex
+#[test]
+fn test_default_handlers_registered() {
+    let registry = OracleRegistry::with_default_handlers();
+    let names: Vec<String> = registry.list().into_iter().map(|info| info.name).collect();
+    assert_eq!(
+        names,
+        vec![
+            "get_pairing_witnesses",
+            "is_third_root",
+            "random_third_root",
+            "third_root",
+            "witness_gen"
+        ]
+    );
 }
 
-pub(crate) fn cast_bigint_to_bignum_limbs(input: &BigInt, num_limbs: u32) -> Vec<String> {
-    // a constant 2^120 as biguint
-    let base: BigInt = BigInt::from(2u32);
-    let exp = 120u32;
-    let shift_constant = base.pow(exp);
-    let mut input_copy = input.clone();
-    // an empty array of size num_limbs of type hex limbs
-    let mut limbs_hex: Vec<String> = vec![];
-    for i in 0..num_limbs {
-        let remainder = &input_copy % &shift_constant;
-        limbs_hex.push(remainder.to_str_radix(16));
-        let quetient: BigInt = input_copy / &shift_constant;
-        input_copy = quetient.clone();
-    }
-    limbs_hex
+#[test]
+fn test_is_third_root_of_one() {
+    use ark_std::One;
+    let inputs = crate::commands::fp12_inputs(&Fq12::one());
+    let registry = OracleRegistry::with_default_handlers();
+    assert_eq!(
+        registry
+            .call("is_third_root", &inputs, &Session::new(0, "", ""))
+            .unwrap(),
+        json!({"values": ["1"]})
+    );
 }
 
-impl Params {
-    // this function takes the foreign call params and returns a Params struct
-    pub fn from_foreign_call_params(inputs: &Vec<ForeignCallParam<String>>) -> Params {
-        let has_multiplicative_inverse_fc = &inputs[0];
-        let has_multiplicative_inverse = get_bool_from_callparam(&has_multiplicative_inverse_fc);
-        let modulus_fc = &inputs[1];
-        // let modulus = gets_limbs(callparam_to_string(modulus_fc));
-        let modulus_str = callparam_to_string(modulus_fc);
-        let modulus = cast_to_biguint(modulus_str);
-        let double_modulus_fc = &inputs[4];
-        let double_modulus = gets_limbs(callparam_to_string(double_modulus_fc));
-        let redc_param_fc = &inputs[5];
-        let redc_param = gets_limbs(callparam_to_string(redc_param_fc));
-
-        Params {
-            has_multiplicative_inverse: has_multiplicative_inverse,
-            modulus: modulus,
-            double_modulus: double_modulus,
-            redc_param: redc_param,
-        }
-    }
+#[test]
+fn test_fq12_inputs_need_three_limbs_each() {
+    use ark_std::One;
+    let mut inputs = crate::commands::fp12_inputs(&Fq12::one());
+    // an extra limb used to be folded into the coefficient
+    inputs[4] = ForeignCallParam::Array(vec!["1".to_string(); 4]);
+    assert_eq!(
+        get_fq12_from_callparam(&inputs),
+        Err(OracleError::WrongShape {
+            index: 4,
+            expected: ParamShape::Array(3),
+            got: ParamShape::Array(4),
+        })
+    );
+    inputs[4] = ForeignCallParam::Single("1".to_string());
+    assert!(matches!(
+        get_fq12_from_callparam(&inputs),
+        Err(OracleError::WrongShape { index: 4, .. })
+    ));
+    // a top limb wider than the 14 bits left of BigNum<3, 254>
+    inputs[4] = ForeignCallParam::Array(vec!["0".to_string(), "0".to_string(), "4000".to_string()]);
+    assert_eq!(
+        get_fq12_from_callparam(&inputs).unwrap_err().to_string(),
+        "bad limb: input 4: limb 2 of BigNum<3, 254> is 15 bits wide, it may hold 14"
+    );
+}
+
+#[test]
+fn test_strict_handlers_reject_non_canonical_inputs() {
+    use crate::registry::Oracle;
+    use ark_std::One;
+    let mut inputs = crate::commands::fp12_inputs(&Fq12::one());
+    // p + 1 reduces to one
+    inputs[0] = crate::request::non_canonical_fq(1);
+    let session = Session::new(0, "", "");
+    assert_eq!(
+        IsThirdRoot { strict: false }.call(&inputs, &session),
+        Ok(json!({"values": ["1"]}))
+    );
+    assert!(matches!(
+        IsThirdRoot { strict: true }.call(&inputs, &session),
+        Err(OracleError::NonCanonical(message)) if message.contains("coefficient 0")
+    ));
+    inputs.swap(0, 9);
+    assert_eq!(
+        IsThirdRoot { strict: true }
+            .call(&inputs, &session)
+            .unwrap_err()
+            .to_string(),
+        "non-canonical value: Fp12 coefficient 9 (c1.c1.c1) is not below the bn254 base field modulus"
+    );
 }
 
-impl std::fmt::Debug for Params {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Params {{ has_multiplicative_inverse: {:?}, modulus: {:?}, double_modulus: {:?}, redc_param: {:?}", self.has_multiplicative_inverse, self.modulus, self.double_modulus, self.redc_param)
+#[test]
+fn test_lenient_decoding_reduces_with_a_warning() {
+    use crate::dispatch::LogBuffer;
+    use ark_std::One;
+    let mut inputs = crate::commands::fp12_inputs(&Fq12::one());
+    inputs[7] = crate::request::non_canonical_fq(5);
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_writer(move || writer.clone())
+        .finish();
+    let decoded =
+        tracing::subscriber::with_default(subscriber, || get_fq12_from_callparam(&inputs));
+
+    let mut expected = Fq12::one();
+    expected.c1.c0.c1 = Fq::from(5u32);
+    assert_eq!(decoded, Ok(expected));
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let line: Value = serde_json::from_str(logs.lines().next().unwrap()).unwrap();
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(line["fields"]["path"], "c1.c0.c1");
+}
+
+#[test]
+fn test_scalar_params_accept_any_hex_spelling() {
+    let single = |value: &str| ForeignCallParam::Single(value.to_string());
+    for (value, expected) in [("", 0), ("0x1", 1), ("01", 1), (" 0X1F\n", 31)] {
+        assert_eq!(
+            get_u32_from_callparam(&single(value)),
+            Ok(expected),
+            "{:?}",
+            value
+        );
     }
+    assert_eq!(get_bool_from_callparam(&single("0x01")), Ok(true));
+    assert_eq!(get_bool_from_callparam(&single("0x")), Ok(false));
+    assert!(get_bool_from_callparam(&single("0x2")).is_err());
+    assert!(get_u32_from_callparam(&single("100000000")).is_err());
+    assert!(get_u32_from_callparam(&single("0xx1")).is_err());
+
+    let limbs = ["0x0A", " ff", "", "0x"];
+    assert_eq!(
+        cast_to_biguint(limbs.to_vec()),
+        Ok(BigUint::from(10u32) + (BigUint::from(255u32) << 120))
+    );
 }
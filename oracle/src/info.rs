@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::dispatch::Dispatcher;
+use crate::ops::WITNESS_ALGORITHM_VERSION;
+
+/// Curves whose pairing witnesses the default oracles compute.
+pub const SUPPORTED_CURVES: [&str; 1] = ["bn254"];
+
+/// Answer of the `version` RPC, lets a test harness check it talks to a compatible oracle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionInfo {
+    pub crate_version: &'static str,
+    pub supported_curves: Vec<&'static str>,
+    pub witness_algorithm_version: u32,
+}
+
+pub fn version_info() -> VersionInfo {
+    VersionInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        supported_curves: SUPPORTED_CURVES.to_vec(),
+        witness_algorithm_version: WITNESS_ALGORITHM_VERSION,
+    }
+}
+
+/// Answer of the `health` RPC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Health {
+    pub status: &'static str,
+    pub oracles: usize,
+    pub sessions: usize,
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+}
+
+pub fn health(dispatcher: &Dispatcher) -> Health {
+    Health {
+        status: "ok",
+        oracles: dispatcher.registry().list().len(),
+        sessions: dispatcher.sessions().len(),
+        in_flight: dispatcher.in_flight(),
+        max_in_flight: dispatcher.limits().max_in_flight,
+    }
+}
+
+#[test]
+fn test_version_info() {
+    let info = serde_json::to_value(version_info()).unwrap();
+    assert_eq!(info["crate_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["supported_curves"], serde_json::json!(["bn254"]));
+    assert_eq!(info["witness_algorithm_version"], WITNESS_ALGORITHM_VERSION);
+}
+
+#[test]
+fn test_health_of_idle_dispatcher() {
+    use crate::registry::OracleRegistry;
+    let dispatcher = Dispatcher::new(OracleRegistry::with_default_handlers());
+    let health = health(&dispatcher);
+    assert_eq!(health.status, "ok");
+    assert_eq!(health.oracles, 5);
+    assert_eq!((health.sessions, health.in_flight), (0, 0));
+}
+
+#[test]
+fn test_list_oracles_describes_layouts() {
+    use crate::registry::{OracleRegistry, ParamShape};
+    let oracles = OracleRegistry::with_default_handlers().list();
+    let witnesses = oracles
+        .iter()
+        .find(|info| info.name == "get_pairing_witnesses")
+        .unwrap();
+    assert_eq!(witnesses.inputs, vec![ParamShape::Array(3); 12]);
+    assert_eq!(witnesses.outputs, vec![ParamShape::Array(36); 2]);
+    assert_eq!(
+        serde_json::to_value(witnesses.outputs[0]).unwrap(),
+        serde_json::json!({ "array": 36 })
+    );
+}
@@ -0,0 +1,516 @@
+use ark_ec::short_weierstrass::{Affine, Projective, SWCurveConfig};
+use ark_ff::{
+    CubicExtField, Fp12Config, Fp12ConfigWrapper, Fp2Config, Fp2ConfigWrapper, Fp6Config,
+    Fp6ConfigWrapper, QuadExtField, Zero,
+};
+use num_bigint::BigUint;
+
+use crate::error::OracleError;
+use crate::foreign_call::ForeignCallParam;
+use crate::handlers::get_bool_from_callparam;
+use crate::limbs::LimbCodec;
+use crate::registry::{check_shape, ParamShape};
+
+/// An arkworks value and the Noir struct of `lib/src` it crosses the foreign call boundary as.
+///
+/// nargo flattens a struct field by field, so e.g. a BN254 `G2Affine { x: Fp2, y: Fp2,
+/// infinity: bool }` is five params: the limbs of `x.c0`, `x.c1`, `y.c0` and `y.c1`, then the
+/// flag. Return values are laid out the same way.
+///
+/// Points have to be on the curve, and base field values below the modulus: [`NoirLayout::decode`]
+/// rejects the ones that aren't like a strict handler, [`NoirLayout::decode_with`] can reduce
+/// them with a warning instead. Errors name the offending field, e.g. `y.c1`.
+pub trait NoirLayout: Sized {
+    /// The params the value is flattened into, in field order.
+    fn shape() -> Vec<ParamShape>;
+
+    /// Appends the params of the value to `params`.
+    fn encode_into(&self, params: &mut Vec<ForeignCallParam<String>>) -> Result<(), OracleError>;
+
+    /// Decodes the value from `params`, which are laid out as [`NoirLayout::shape`]. `path` is
+    /// the field the value sits at, used in errors and warnings.
+    fn decode_at(
+        params: &[ForeignCallParam<String>],
+        path: &str,
+        strict: bool,
+    ) -> Result<Self, OracleError>;
+
+    fn encode(&self) -> Result<Vec<ForeignCallParam<String>>, OracleError> {
+        let mut params = vec![];
+        self.encode_into(&mut params)?;
+        Ok(params)
+    }
+
+    /// Checks `params` against [`NoirLayout::shape`] and decodes them, rejecting non-canonical
+    /// base field values.
+    fn decode(params: &[ForeignCallParam<String>]) -> Result<Self, OracleError> {
+        Self::decode_with(params, true)
+    }
+
+    /// Like [`NoirLayout::decode`], unless `strict` is off: then base field values that aren't
+    /// below the modulus are reduced with a warning, as the handlers do.
+    fn decode_with(params: &[ForeignCallParam<String>], strict: bool) -> Result<Self, OracleError> {
+        check_shape(&Self::shape(), params)?;
+        Self::decode_at(params, "", strict)
+    }
+}
+
+// `field` of the value at `path`
+fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+// prefixes the message of a decoding error with the field it happened at
+fn at_path(path: &str, err: OracleError) -> OracleError {
+    if path.is_empty() {
+        return err;
+    }
+    match err {
+        OracleError::BadLimb(message) => OracleError::BadLimb(format!("{}: {}", path, message)),
+        OracleError::NonCanonical(message) => {
+            OracleError::NonCanonical(format!("{}: {}", path, message))
+        }
+        err => err,
+    }
+}
+
+// decodes the fields of a struct one after the other, each taking as many params as its shape
+struct Fields<'a> {
+    params: &'a [ForeignCallParam<String>],
+    path: &'a str,
+    strict: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn new(params: &'a [ForeignCallParam<String>], path: &'a str, strict: bool) -> Self {
+        Fields {
+            params,
+            path,
+            strict,
+        }
+    }
+
+    fn next<T: NoirLayout>(&mut self, field: &str) -> Result<T, OracleError> {
+        let (params, rest) = self.params.split_at(T::shape().len());
+        self.params = rest;
+        T::decode_at(params, &field_path(self.path, field), self.strict)
+    }
+
+    fn next_bool(&mut self, field: &str) -> Result<bool, OracleError> {
+        let (param, rest) = self.params.split_first().expect("params match the shape");
+        self.params = rest;
+        get_bool_from_callparam(param).map_err(|err| at_path(&field_path(self.path, field), err))
+    }
+}
+
+fn encode_bool(value: bool, params: &mut Vec<ForeignCallParam<String>>) {
+    params.push(ForeignCallParam::Single(
+        if value { "1" } else { "0" }.to_string(),
+    ));
+}
+
+macro_rules! impl_base_field {
+    ($field:ty, $codec:expr, $curve:expr) => {
+        impl NoirLayout for $field {
+            fn shape() -> Vec<ParamShape> {
+                vec![$codec.shape()]
+            }
+
+            fn encode_into(
+                &self,
+                params: &mut Vec<ForeignCallParam<String>>,
+            ) -> Result<(), OracleError> {
+                let value: BigUint = (*self).into();
+                params.push(ForeignCallParam::Array($codec.encode(&value)?));
+                Ok(())
+            }
+
+            fn decode_at(
+                params: &[ForeignCallParam<String>],
+                path: &str,
+                strict: bool,
+            ) -> Result<Self, OracleError> {
+                let codec = $codec;
+                let value = codec
+                    .decode_param(&params[0])
+                    .map_err(|err| at_path(path, err))?;
+                if !codec.is_canonical(&value) {
+                    if strict {
+                        return Err(at_path(
+                            path,
+                            OracleError::NonCanonical(format!(
+                                "not below the {} base field modulus",
+                                $curve
+                            )),
+                        ));
+                    }
+                    tracing::warn!(
+                        path,
+                        "{} is not below the {} base field modulus, reducing it",
+                        path,
+                        $curve
+                    );
+                }
+                // `from` reduces modulo p
+                Ok(Self::from(value))
+            }
+        }
+    };
+}
+
+impl_base_field!(ark_bn254::Fq, LimbCodec::bn254_fq(), "bn254");
+impl_base_field!(
+    ark_test_curves::bls12_381::Fq,
+    LimbCodec::bls12_381_fq(),
+    "bls12_381"
+);
+
+/// `Fp2 { c0, c1 }`
+impl<P: Fp2Config> NoirLayout for QuadExtField<Fp2ConfigWrapper<P>>
+where
+    P::Fp: NoirLayout,
+{
+    fn shape() -> Vec<ParamShape> {
+        [P::Fp::shape(), P::Fp::shape()].concat()
+    }
+
+    fn encode_into(&self, params: &mut Vec<ForeignCallParam<String>>) -> Result<(), OracleError> {
+        self.c0.encode_into(params)?;
+        self.c1.encode_into(params)
+    }
+
+    fn decode_at(
+        params: &[ForeignCallParam<String>],
+        path: &str,
+        strict: bool,
+    ) -> Result<Self, OracleError> {
+        let mut fields = Fields::new(params, path, strict);
+        Ok(Self::new(fields.next("c0")?, fields.next("c1")?))
+    }
+}
+
+/// `Fp6 { c0, c1, c2 }`
+impl<P: Fp6Config> NoirLayout for CubicExtField<Fp6ConfigWrapper<P>>
+where
+    ark_ff::Fp2<P::Fp2Config>: NoirLayout,
+{
+    fn shape() -> Vec<ParamShape> {
+        let fp2 = ark_ff::Fp2::<P::Fp2Config>::shape();
+        [fp2.clone(), fp2.clone(), fp2].concat()
+    }
+
+    fn encode_into(&self, params: &mut Vec<ForeignCallParam<String>>) -> Result<(), OracleError> {
+        self.c0.encode_into(params)?;
+        self.c1.encode_into(params)?;
+        self.c2.encode_into(params)
+    }
+
+    fn decode_at(
+        params: &[ForeignCallParam<String>],
+        path: &str,
+        strict: bool,
+    ) -> Result<Self, OracleError> {
+        let mut fields = Fields::new(params, path, strict);
+        Ok(Self::new(
+            fields.next("c0")?,
+            fields.next("c1")?,
+            fields.next("c2")?,
+        ))
+    }
+}
+
+/// `Fp12 { c0, c1 }`
+impl<P: Fp12Config> NoirLayout for QuadExtField<Fp12ConfigWrapper<P>>
+where
+    ark_ff::Fp6<P::Fp6Config>: NoirLayout,
+{
+    fn shape() -> Vec<ParamShape> {
+        let fp6 = ark_ff::Fp6::<P::Fp6Config>::shape();
+        [fp6.clone(), fp6].concat()
+    }
+
+    fn encode_into(&self, params: &mut Vec<ForeignCallParam<String>>) -> Result<(), OracleError> {
+        self.c0.encode_into(params)?;
+        self.c1.encode_into(params)
+    }
+
+    fn decode_at(
+        params: &[ForeignCallParam<String>],
+        path: &str,
+        strict: bool,
+    ) -> Result<Self, OracleError> {
+        let mut fields = Fields::new(params, path, strict);
+        Ok(Self::new(fields.next("c0")?, fields.next("c1")?))
+    }
+}
+
+/// `G1Affine { x, y, infinity }` and `G2Affine { x, y, infinity }`.
+///
+/// The point at infinity is written with zero coordinates, and read whatever its coordinates
+/// are. Any other point has to be on the curve and in the prime order subgroup.
+impl<P: SWCurveConfig> NoirLayout for Affine<P>
+where
+    P::BaseField: NoirLayout,
+{
+    fn shape() -> Vec<ParamShape> {
+        let coordinate = P::BaseField::shape();
+        [coordinate.clone(), coordinate, vec![ParamShape::Single]].concat()
+    }
+
+    fn encode_into(&self, params: &mut Vec<ForeignCallParam<String>>) -> Result<(), OracleError> {
+        let (x, y) = if self.infinity {
+            (P::BaseField::zero(), P::BaseField::zero())
+        } else {
+            (self.x, self.y)
+        };
+        x.encode_into(params)?;
+        y.encode_into(params)?;
+        encode_bool(self.infinity, params);
+        Ok(())
+    }
+
+    fn decode_at(
+        params: &[ForeignCallParam<String>],
+        path: &str,
+        strict: bool,
+    ) -> Result<Self, OracleError> {
+        let mut fields = Fields::new(params, path, strict);
+        let (x, y) = (fields.next("x")?, fields.next("y")?);
+        if fields.next_bool("infinity")? {
+            return Ok(Affine::identity());
+        }
+        let point = Affine::new_unchecked(x, y);
+        check_point(&point, path)?;
+        Ok(point)
+    }
+}
+
+// a point that isn't the point at infinity has to be on the curve and in the prime order subgroup
+fn check_point<P: SWCurveConfig>(point: &Affine<P>, path: &str) -> Result<(), OracleError> {
+    let problem = if !point.is_on_curve() {
+        "the point is not on the curve"
+    } else if !point.is_in_correct_subgroup_assuming_on_curve() {
+        "the point is not in the prime order subgroup"
+    } else {
+        return Ok(());
+    };
+    Err(at_path(
+        path,
+        OracleError::NonCanonical(problem.to_string()),
+    ))
+}
+
+/// `G2Projective { x, y, z }`, in Jacobian coordinates like arkworks' `Projective`.
+///
+/// Any point with `z = 0` is the point at infinity, the others are checked like affine ones.
+impl<P: SWCurveConfig> NoirLayout for Projective<P>
+where
+    P::BaseField: NoirLayout,
+{
+    fn shape() -> Vec<ParamShape> {
+        let coordinate = P::BaseField::shape();
+        [coordinate.clone(), coordinate.clone(), coordinate].concat()
+    }
+
+    fn encode_into(&self, params: &mut Vec<ForeignCallParam<String>>) -> Result<(), OracleError> {
+        self.x.encode_into(params)?;
+        self.y.encode_into(params)?;
+        self.z.encode_into(params)
+    }
+
+    fn decode_at(
+        params: &[ForeignCallParam<String>],
+        path: &str,
+        strict: bool,
+    ) -> Result<Self, OracleError> {
+        let mut fields = Fields::new(params, path, strict);
+        let (x, y, z): (P::BaseField, _, P::BaseField) =
+            (fields.next("x")?, fields.next("y")?, fields.next("z")?);
+        if z.is_zero() {
+            return Ok(Projective::zero());
+        }
+        let point = Projective::new_unchecked(x, y, z);
+        check_point(&Affine::from(point), path)?;
+        Ok(point)
+    }
+}
+
+#[test]
+fn test_layouts_match_the_noir_structs() {
+    use ark_bn254::{Fq12, G1Affine, G2Affine, G2Projective};
+    use ark_test_curves::bls12_381;
+
+    let bn_fp = ParamShape::Array(3);
+    let bls_fp = ParamShape::Array(4);
+    let flag = ParamShape::Single;
+    assert_eq!(ark_bn254::Fq2::shape(), vec![bn_fp; 2]);
+    assert_eq!(ark_bn254::Fq6::shape(), vec![bn_fp; 6]);
+    assert_eq!(Fq12::shape(), vec![bn_fp; 12]);
+    assert_eq!(G1Affine::shape(), vec![bn_fp, bn_fp, flag]);
+    assert_eq!(G2Affine::shape(), [vec![bn_fp; 4], vec![flag]].concat());
+    assert_eq!(G2Projective::shape(), vec![bn_fp; 6]);
+    assert_eq!(bls12_381::Fq6::shape(), vec![bls_fp; 6]);
+    assert_eq!(
+        bls12_381::G2Affine::shape(),
+        [vec![bls_fp; 4], vec![flag]].concat()
+    );
+    assert_eq!(bls12_381::G1Projective::shape(), vec![bls_fp; 3]);
+}
+
+// the flattening of an Fp12 is the one the existing handlers read and write
+#[test]
+fn test_fp12_layout_matches_the_handlers() {
+    use ark_bn254::Fq12;
+    use ark_std::{rand::SeedableRng, UniformRand};
+
+    let mut rng = ark_std::rand::rngs::StdRng::seed_from_u64(3);
+    let value = Fq12::rand(&mut rng);
+    let params = value.encode().unwrap();
+    assert_eq!(params, crate::commands::fp12_inputs(&value));
+    assert_eq!(Fq12::decode(&params), Ok(value));
+}
+
+#[test]
+fn test_round_trip_points_of_both_curves() {
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_std::{rand::SeedableRng, UniformRand};
+    use ark_test_curves::bls12_381;
+
+    fn round_trip<T: NoirLayout + PartialEq + std::fmt::Debug>(value: T) {
+        let params = value.encode().unwrap();
+        assert_eq!(params.len(), T::shape().len());
+        assert_eq!(T::decode(&params), Ok(value));
+    }
+
+    let mut rng = ark_std::rand::rngs::StdRng::seed_from_u64(5);
+    round_trip(ark_bn254::Fq2::rand(&mut rng));
+    round_trip(bls12_381::Fq6::rand(&mut rng));
+    round_trip(bls12_381::Fq12::rand(&mut rng));
+    round_trip(ark_bn254::G1Affine::rand(&mut rng));
+    round_trip(ark_bn254::G2Affine::rand(&mut rng));
+    round_trip(bls12_381::G1Affine::rand(&mut rng));
+    round_trip(bls12_381::G2Affine::rand(&mut rng));
+    round_trip(ark_bn254::G2Projective::rand(&mut rng));
+    round_trip(bls12_381::G2Projective::rand(&mut rng));
+
+    // a scaled Jacobian point is the same point
+    let point = bls12_381::G1Affine::generator().into_group();
+    let mut scaled = point;
+    let two = bls12_381::Fq::from(2u32);
+    scaled.x *= two * two;
+    scaled.y *= two * two * two;
+    scaled.z *= two;
+    assert_eq!(
+        bls12_381::G1Projective::decode(&scaled.encode().unwrap()),
+        Ok(point)
+    );
+    assert_eq!(scaled.into_affine(), point.into_affine());
+}
+
+#[test]
+fn test_points_at_infinity() {
+    use ark_ec::AffineRepr;
+    use ark_test_curves::bls12_381;
+
+    let identity = ark_bn254::G2Affine::identity();
+    let params = identity.encode().unwrap();
+    assert_eq!(params[4], ForeignCallParam::Single("1".to_string()));
+    assert!(params[..4]
+        .iter()
+        .all(|param| param.get_values().iter().all(|limb| limb == "0")));
+    assert_eq!(ark_bn254::G2Affine::decode(&params), Ok(identity));
+
+    // Noir leaves the coordinates of the point at infinity as they were
+    let mut params = bls12_381::G1Affine::generator().encode().unwrap();
+    params[2] = ForeignCallParam::Single("1".to_string());
+    assert_eq!(
+        bls12_381::G1Affine::decode(&params),
+        Ok(bls12_381::G1Affine::identity())
+    );
+
+    // `G2Projective::from_affine` keeps x and y and sets z to zero
+    let mut params = ark_bn254::G2Affine::generator().encode().unwrap();
+    params.truncate(4);
+    params.extend(ark_bn254::Fq2::zero().encode().unwrap());
+    assert_eq!(
+        ark_bn254::G2Projective::decode(&params),
+        Ok(ark_bn254::G2Projective::zero())
+    );
+    assert_eq!(
+        ark_bn254::G2Projective::zero().encode().unwrap()[4..],
+        ark_bn254::Fq2::zero().encode().unwrap()[..]
+    );
+}
+
+#[test]
+fn test_decoding_errors_name_the_field() {
+    use ark_ec::AffineRepr;
+    use ark_ff::PrimeField;
+
+    let mut params = ark_bn254::G2Affine::generator().encode().unwrap();
+    let modulus: BigUint = ark_bn254::Fq::MODULUS.into();
+    params[3] = ForeignCallParam::Array(LimbCodec::bn254_fq().encode(&modulus).unwrap());
+    assert_eq!(
+        ark_bn254::G2Affine::decode(&params)
+            .unwrap_err()
+            .to_string(),
+        "non-canonical value: y.c1: not below the bn254 base field modulus"
+    );
+
+    params[3] = ForeignCallParam::Array(vec!["0".to_string(), "0".to_string(), "4000".to_string()]);
+    assert_eq!(
+        ark_bn254::G2Affine::decode(&params)
+            .unwrap_err()
+            .to_string(),
+        "bad limb: y.c1: limb 2 of BigNum<3, 254> is 15 bits wide, it may hold 14"
+    );
+
+    params[3] = ark_bn254::Fq::from(1u32).encode().unwrap().remove(0);
+    assert_eq!(
+        ark_bn254::G2Affine::decode(&params),
+        Err(OracleError::NonCanonical(
+            "the point is not on the curve".to_string()
+        ))
+    );
+
+    params[4] = ForeignCallParam::Single("2".to_string());
+    assert_eq!(
+        ark_bn254::G2Affine::decode(&params)
+            .unwrap_err()
+            .to_string(),
+        "non-canonical value: infinity: 2 is not a bool"
+    );
+    assert!(matches!(
+        ark_bn254::G2Affine::decode(&params[..4]),
+        Err(OracleError::WrongArity {
+            expected: 5,
+            got: 4
+        })
+    ));
+}
+
+// like the handlers, a lenient decoding reduces what a strict one rejects
+#[test]
+fn test_lenient_decoding_reduces_base_field_values() {
+    use ark_ec::AffineRepr;
+    use ark_ff::PrimeField;
+
+    let point = ark_bn254::G1Affine::generator();
+    let mut params = point.encode().unwrap();
+    let modulus: BigUint = ark_bn254::Fq::MODULUS.into();
+    params[0] = ForeignCallParam::Array(
+        LimbCodec::bn254_fq()
+            .encode(&(modulus + BigUint::from(point.x)))
+            .unwrap(),
+    );
+    assert!(matches!(
+        ark_bn254::G1Affine::decode(&params),
+        Err(OracleError::NonCanonical(_))
+    ));
+    assert_eq!(ark_bn254::G1Affine::decode_with(&params, false), Ok(point));
+    assert!(ark_bn254::G1Affine::decode_with(&params, true).is_err());
+}
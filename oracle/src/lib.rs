@@ -0,0 +1,20 @@
+pub mod bindings;
+pub mod cache;
+pub mod commands;
+pub mod declarations;
+pub mod dispatch;
+pub mod error;
+pub mod foreign_call;
+pub mod handlers;
+pub mod info;
+pub mod layout;
+pub mod limbs;
+pub mod loadtest;
+pub mod metrics;
+pub mod ops;
+pub mod packages;
+pub mod registry;
+pub mod replay;
+pub mod request;
+pub mod server;
+pub mod session;
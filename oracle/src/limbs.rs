@@ -0,0 +1,304 @@
+use ark_ff::PrimeField;
+use num_bigint::BigUint;
+use num_traits::{Num, One, Zero};
+use std::fmt;
+
+use crate::error::OracleError;
+use crate::foreign_call::ForeignCallParam;
+use crate::registry::ParamShape;
+
+/// Width of a noir-bignum limb.
+pub const LIMB_BITS: usize = 120;
+
+/// How a noir-bignum `BigNum<N, MOD_BITS>` crosses the foreign call boundary: `N` hex limbs of
+/// `limb_bits` bits, least significant first, the last one holding the remaining
+/// `MOD_BITS - (N - 1) * limb_bits` bits.
+///
+/// Both directions check the widths, a value that doesn't fit is an error rather than being
+/// truncated or folded into the neighbouring limb.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimbCodec {
+    limb_bits: usize,
+    num_limbs: usize,
+    modulus: BigUint,
+    modulus_bits: usize,
+}
+
+impl LimbCodec {
+    /// A codec for values below `modulus`, as `num_limbs` limbs of `limb_bits` bits.
+    ///
+    /// Panics if the modulus doesn't fit in the limbs, which is a bug in the caller.
+    pub fn new(limb_bits: usize, num_limbs: usize, modulus: BigUint) -> Self {
+        assert!(limb_bits > 0 && num_limbs > 0 && !modulus.is_zero());
+        let modulus_bits = (&modulus - 1u32).bits() as usize;
+        assert!(
+            modulus_bits <= limb_bits * num_limbs,
+            "a {} bit modulus doesn't fit in {} limbs of {} bits",
+            modulus_bits,
+            num_limbs,
+            limb_bits
+        );
+        LimbCodec {
+            limb_bits,
+            num_limbs,
+            modulus,
+            modulus_bits,
+        }
+    }
+
+    /// A codec for any value the limbs can hold, i.e. modulo `2^(limb_bits * num_limbs)`.
+    pub fn unbounded(limb_bits: usize, num_limbs: usize) -> Self {
+        Self::new(
+            limb_bits,
+            num_limbs,
+            BigUint::one() << (limb_bits * num_limbs),
+        )
+    }
+
+    /// The `BigNum<N, MOD_BITS>` of a prime field.
+    pub fn for_field<F: PrimeField>() -> Self {
+        let modulus: BigUint = F::MODULUS.into();
+        let num_limbs = (modulus.bits() as usize).div_ceil(LIMB_BITS);
+        Self::new(LIMB_BITS, num_limbs, modulus)
+    }
+
+    /// `BigNum<3, 254>`, the BN254 base field used by `lib/src/bn`.
+    pub fn bn254_fq() -> Self {
+        Self::for_field::<ark_bn254::Fq>()
+    }
+
+    /// `BigNum<4, 381>`, the BLS12-381 base field used by `lib/src/bls`.
+    pub fn bls12_381_fq() -> Self {
+        Self::for_field::<ark_test_curves::bls12_381::Fq>()
+    }
+
+    /// `Mod_p12m1`, i.e. `BigNum<26, 3044>` modulo `p^12 - 1` of BN254.
+    pub fn bn254_mod_p12m1() -> Self {
+        Self::new(LIMB_BITS, 26, bn254_p12_minus_one())
+    }
+
+    /// `Mod_h`, i.e. `BigNum<24, 2790>` modulo `(p^12 - 1) / r` of BN254.
+    pub fn bn254_mod_h() -> Self {
+        let r: BigUint = ark_bn254::Fr::MODULUS.into();
+        Self::new(LIMB_BITS, 24, bn254_p12_minus_one() / r)
+    }
+
+    pub fn num_limbs(&self) -> usize {
+        self.num_limbs
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// The `MOD_BITS` of the BigNum.
+    pub fn modulus_bits(&self) -> usize {
+        self.modulus_bits
+    }
+
+    /// How a value is passed as a foreign call param.
+    pub fn shape(&self) -> ParamShape {
+        ParamShape::Array(self.num_limbs)
+    }
+
+    /// Whether `value` is reduced, i.e. below the modulus.
+    pub fn is_canonical(&self, value: &BigUint) -> bool {
+        value < &self.modulus
+    }
+
+    // bits the limb at `index` may hold
+    fn limb_width(&self, index: usize) -> usize {
+        if index + 1 == self.num_limbs {
+            self.modulus_bits - self.limb_bits * (self.num_limbs - 1)
+        } else {
+            self.limb_bits
+        }
+    }
+
+    /// The hex limbs of `value`, which has to fit in `MOD_BITS`.
+    pub fn encode(&self, value: &BigUint) -> Result<Vec<String>, OracleError> {
+        if value.bits() as usize > self.modulus_bits {
+            return Err(OracleError::NonCanonical(format!(
+                "a {} bit value doesn't fit in {}",
+                value.bits(),
+                self
+            )));
+        }
+        Ok(self.split(value))
+    }
+
+    // the limbs of a value that fits in MOD_BITS
+    fn split(&self, value: &BigUint) -> Vec<String> {
+        let mask = (BigUint::one() << self.limb_bits) - 1u32;
+        (0..self.num_limbs)
+            .map(|index| ((value >> (index * self.limb_bits)) & &mask).to_str_radix(16))
+            .collect()
+    }
+
+    /// The value held by hex `limbs`, which have to be exactly `N`, each within its width.
+    pub fn decode<S: AsRef<str>>(&self, limbs: &[S]) -> Result<BigUint, OracleError> {
+        if limbs.len() != self.num_limbs {
+            return Err(OracleError::BadLimb(format!(
+                "{} takes {} limbs, got {}",
+                self,
+                self.num_limbs,
+                limbs.len()
+            )));
+        }
+        let mut value = BigUint::zero();
+        for (index, limb) in limbs.iter().enumerate() {
+            let limb = parse_hex(limb.as_ref())?;
+            let width = self.limb_width(index);
+            if limb.bits() as usize > width {
+                return Err(OracleError::BadLimb(format!(
+                    "limb {} of {} is {} bits wide, it may hold {}",
+                    index,
+                    self,
+                    limb.bits(),
+                    width
+                )));
+            }
+            value |= limb << (index * self.limb_bits);
+        }
+        Ok(value)
+    }
+
+    /// [`LimbCodec::decode`] of an array param.
+    pub fn decode_param(&self, param: &ForeignCallParam<String>) -> Result<BigUint, OracleError> {
+        match param {
+            ForeignCallParam::Array(limbs) => self.decode(limbs),
+            ForeignCallParam::Single(_) => Err(OracleError::BadLimb(format!(
+                "{} is passed as an array of limbs, got a single value",
+                self
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LimbCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BigNum<{}, {}>", self.num_limbs, self.modulus_bits)
+    }
+}
+
+fn bn254_p12_minus_one() -> BigUint {
+    let p: BigUint = ark_bn254::Fq::MODULUS.into();
+    p.pow(12) - 1u32
+}
+
+/// Parses a field value or limb as nargo and other tools write it: hex digits of either case,
+/// optionally `0x` prefixed, zero padded and surrounded by whitespace. An empty value is zero.
+pub fn parse_hex(value: &str) -> Result<BigUint, OracleError> {
+    let trimmed = value.trim();
+    let digits = trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .unwrap_or(trimmed);
+    // from_str_radix would also take a sign and underscores
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(OracleError::BadLimb(format!(
+            "{:?} is not a hex value",
+            value
+        )));
+    }
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(BigUint::zero());
+    }
+    Ok(BigUint::from_str_radix(digits, 16).expect("only hex digits are left"))
+}
+
+// the shapes the Noir library declares
+#[test]
+fn test_codecs_match_the_noir_bignums() {
+    let shapes: Vec<(usize, usize)> = [
+        LimbCodec::bn254_fq(),
+        LimbCodec::bls12_381_fq(),
+        LimbCodec::bn254_mod_h(),
+        LimbCodec::bn254_mod_p12m1(),
+    ]
+    .iter()
+    .map(|codec| (codec.num_limbs(), codec.modulus_bits()))
+    .collect();
+    assert_eq!(shapes, vec![(3, 254), (4, 381), (24, 2790), (26, 3044)]);
+}
+
+#[test]
+fn test_round_trip_and_widths() {
+    let codec = LimbCodec::bn254_fq();
+    let largest = codec.modulus() - 1u32;
+    let limbs = codec.encode(&largest).unwrap();
+    assert_eq!(limbs.len(), 3);
+    assert_eq!(codec.decode(&limbs).unwrap(), largest);
+    assert_eq!(codec.decode(&["", "0", "000"]).unwrap(), BigUint::zero());
+
+    // p + 1 still fits in 254 bits, it's up to the caller whether that's acceptable
+    let above = codec.modulus() + 1u32;
+    assert!(!codec.is_canonical(&codec.decode(&codec.encode(&above).unwrap()).unwrap()));
+
+    assert!(codec.encode(&(BigUint::one() << 254)).is_err());
+    let wide = format!("1{}", "0".repeat(30));
+    let err = codec.decode(&[wide.as_str(), "0", "0"]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "bad limb: limb 0 of BigNum<3, 254> is 121 bits wide, it may hold 120"
+    );
+    assert!(codec.decode(&["0", "0", "4000"]).is_err());
+    assert!(codec.decode(&["0", "0", "3fff"]).is_ok());
+    assert!(codec.decode(&["0", "0"]).is_err());
+    assert!(codec.decode(&["0", "0", "xyz"]).is_err());
+
+    let unbounded = LimbCodec::unbounded(LIMB_BITS, 2);
+    let max = (BigUint::one() << 240) - 1u32;
+    assert_eq!(
+        unbounded.decode(&unbounded.encode(&max).unwrap()).unwrap(),
+        max
+    );
+}
+
+#[test]
+fn test_parse_hex_spellings() {
+    let ff = BigUint::from(255u32);
+    for spelling in [
+        "ff", "FF", "0xff", "0XfF", "00ff", "0x00FF", " ff\n", "\t0xff ",
+    ] {
+        assert_eq!(parse_hex(spelling), Ok(ff.clone()), "{:?}", spelling);
+    }
+    for zero in ["", "0", "0000", "0x", "0x0", "  "] {
+        assert_eq!(parse_hex(zero), Ok(BigUint::zero()), "{:?}", zero);
+    }
+    for garbage in ["x1", "0x0x1", "-1", "+1", "1_0", "0x 1", "1 2", "g"] {
+        assert!(parse_hex(garbage).is_err(), "{:?}", garbage);
+    }
+    assert_eq!(
+        parse_hex("0xg").unwrap_err().to_string(),
+        "bad limb: \"0xg\" is not a hex value"
+    );
+}
+
+// every path a field value comes in through reads it with parse_hex, so a spelling can't parse
+// in one place and fail (or key differently) in another
+#[test]
+fn test_hex_spellings_agree_everywhere() {
+    let spellings = ["0X1F", "0x1f", "1f", " 001F "];
+    for spelling in spellings {
+        assert_eq!(
+            crate::commands::parse_fq(spelling).unwrap(),
+            ark_bn254::Fq::from(31u32),
+            "{:?}",
+            spelling
+        );
+        assert_eq!(
+            crate::cache::inputs_hash(&[ForeignCallParam::Single(spelling.to_string())]),
+            crate::cache::inputs_hash(&[ForeignCallParam::Single("1f".to_string())]),
+            "{:?}",
+            spelling
+        );
+        assert_eq!(
+            crate::handlers::cast_to_biguint(vec![spelling]),
+            Ok(BigUint::from(31u32)),
+            "{:?}",
+            spelling
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use ark_bn254::Fq12;
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use ark_std::UniformRand;
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::rpc_params;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::commands::fp12_inputs;
+use crate::ops::witness_generator::{WitnessGenerator, WitnessGeneratorTrait};
+use crate::replay::RecordedCall;
+use crate::request::RequestData;
+
+/// How hard to drive the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadConfig {
+    /// Calls in flight at once.
+    pub concurrency: usize,
+    /// Calls sent in total, cycling through the corpus.
+    pub requests: usize,
+    /// How long a single call may take before it counts as an error.
+    pub timeout: Duration,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        LoadConfig {
+            concurrency: 4,
+            requests: 100,
+            timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// `size` requests alternating between `get_pairing_witnesses` of a random Fp12 and
+/// `third_root` of a random cube, drawn from an RNG seeded by `seed`.
+///
+/// Every request is different, so a server with a cache only answers a repeated corpus from it
+/// once `requests` exceeds `size`.
+pub fn generate_corpus(seed: u64, size: usize) -> Vec<RequestData> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..size)
+        .map(|i| {
+            let (function, value) = if i % 2 == 0 {
+                ("get_pairing_witnesses", Fq12::rand(&mut rng))
+            } else {
+                (
+                    "third_root",
+                    WitnessGenerator::rand_third_root_from(&mut rng),
+                )
+            };
+            RequestData {
+                session_id: i as u64,
+                function: function.to_string(),
+                inputs: fp12_inputs(&value),
+                root_path: String::new(),
+                package_name: String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Reads a corpus from a JSONL file of requests, or of calls written by `serve --record`.
+pub fn load_corpus(path: impl AsRef<Path>) -> anyhow::Result<Vec<RequestData>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)?;
+    let mut corpus = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_error =
+            |err: serde_json::Error| anyhow::anyhow!("{}:{}: {}", path.display(), index + 1, err);
+        let value: Value = serde_json::from_str(line).map_err(line_error)?;
+        let request = if value.get("session_id").is_some() {
+            serde_json::from_value(value).map_err(line_error)?
+        } else {
+            let call: RecordedCall = serde_json::from_value(value).map_err(line_error)?;
+            RequestData {
+                session_id: index as u64,
+                function: call.function,
+                inputs: call.inputs,
+                root_path: String::new(),
+                package_name: String::new(),
+            }
+        };
+        corpus.push(request);
+    }
+    if corpus.is_empty() {
+        anyhow::bail!("{} holds no requests", path.display());
+    }
+    Ok(corpus)
+}
+
+/// Latencies and failures of the calls to one oracle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FunctionReport {
+    pub calls: usize,
+    pub errors: usize,
+    #[serde(serialize_with = "serialize_millis")]
+    pub p50: Duration,
+    #[serde(serialize_with = "serialize_millis")]
+    pub p99: Duration,
+    #[serde(serialize_with = "serialize_millis")]
+    pub max: Duration,
+}
+
+impl FunctionReport {
+    fn from_samples(mut latencies: Vec<Duration>, errors: usize) -> Self {
+        latencies.sort();
+        FunctionReport {
+            calls: latencies.len(),
+            errors,
+            p50: percentile(&latencies, 50),
+            p99: percentile(&latencies, 99),
+            max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        match self.calls {
+            0 => 0.0,
+            calls => self.errors as f64 / calls as f64,
+        }
+    }
+}
+
+// nearest rank percentile of sorted samples
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    value: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.as_secs_f64() * 1000.0)
+}
+
+/// Outcome of a [`run`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadReport {
+    pub concurrency: usize,
+    #[serde(serialize_with = "serialize_millis")]
+    pub elapsed: Duration,
+    /// Completed calls per second, failed ones included.
+    pub throughput: f64,
+    pub total: FunctionReport,
+    pub functions: BTreeMap<String, FunctionReport>,
+    /// How often each error message came back.
+    pub error_messages: BTreeMap<String, usize>,
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} calls in {:.2?} at concurrency {}: {:.2} calls/s",
+            self.total.calls, self.elapsed, self.concurrency, self.throughput
+        )?;
+        writeln!(
+            f,
+            "{:<24} {:>7} {:>7} {:>12} {:>12} {:>12}",
+            "function", "calls", "errors", "p50", "p99", "max"
+        )?;
+        let rows = self
+            .functions
+            .iter()
+            .map(|(name, report)| (name.as_str(), report));
+        for (function, report) in rows.chain([("total", &self.total)]) {
+            writeln!(
+                f,
+                "{:<24} {:>7} {:>6.1}% {:>12.2?} {:>12.2?} {:>12.2?}",
+                function,
+                report.calls,
+                report.error_rate() * 100.0,
+                report.p50,
+                report.p99,
+                report.max
+            )?;
+        }
+        for (message, count) in &self.error_messages {
+            writeln!(f, "{} x {}", count, message)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    latencies: BTreeMap<String, (Vec<Duration>, usize)>,
+    error_messages: BTreeMap<String, usize>,
+}
+
+/// Sends `config.requests` calls from `corpus` to the oracle at `url`, `config.concurrency` at a
+/// time, and reports how it held up.
+pub async fn run(
+    url: &str,
+    corpus: &[RequestData],
+    config: LoadConfig,
+) -> anyhow::Result<LoadReport> {
+    anyhow::ensure!(!corpus.is_empty(), "the corpus is empty");
+    let client = HttpClientBuilder::default()
+        .request_timeout(config.timeout)
+        .build(url)?;
+    let next = Arc::new(AtomicUsize::new(0));
+    let samples = Arc::new(Mutex::new(Samples::default()));
+    let corpus = Arc::new(corpus.to_vec());
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..config.concurrency.max(1))
+        .map(|_| {
+            let (client, next, samples, corpus) = (
+                client.clone(),
+                next.clone(),
+                samples.clone(),
+                corpus.clone(),
+            );
+            tokio::spawn(async move {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= config.requests {
+                        break;
+                    }
+                    let request = &corpus[index % corpus.len()];
+                    let call_started = Instant::now();
+                    let result = call(&client, request).await;
+                    let took = call_started.elapsed();
+
+                    let mut samples = samples.lock().unwrap();
+                    let (latencies, errors) = samples
+                        .latencies
+                        .entry(request.function.clone())
+                        .or_default();
+                    latencies.push(took);
+                    if let Err(message) = result {
+                        *errors += 1;
+                        *samples.error_messages.entry(message).or_default() += 1;
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.await?;
+    }
+    let elapsed = started.elapsed();
+
+    let samples = std::mem::take(&mut *samples.lock().unwrap());
+    let mut all_latencies = vec![];
+    let mut all_errors = 0;
+    let mut functions = BTreeMap::new();
+    for (function, (latencies, errors)) in samples.latencies {
+        all_latencies.extend_from_slice(&latencies);
+        all_errors += errors;
+        functions.insert(function, FunctionReport::from_samples(latencies, errors));
+    }
+    let total = FunctionReport::from_samples(all_latencies, all_errors);
+    Ok(LoadReport {
+        concurrency: config.concurrency,
+        elapsed,
+        throughput: total.calls as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        total,
+        functions,
+        error_messages: samples.error_messages,
+    })
+}
+
+async fn call(client: &HttpClient, request: &RequestData) -> Result<Value, String> {
+    client
+        .request("resolve_foreign_call", rpc_params![request])
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[test]
+fn test_percentile() {
+    let samples: Vec<Duration> = (1..=200).map(Duration::from_millis).collect();
+    assert_eq!(percentile(&samples, 50), Duration::from_millis(100));
+    assert_eq!(percentile(&samples, 99), Duration::from_millis(198));
+    assert_eq!(percentile(&samples[..1], 99), Duration::from_millis(1));
+    assert_eq!(percentile(&[], 50), Duration::ZERO);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_run_against_a_server() {
+    use crate::dispatch::Dispatcher;
+    use crate::registry::OracleRegistry;
+    use crate::server::{rpc_module, serve_tcp};
+
+    let module = rpc_module(Dispatcher::new(OracleRegistry::with_default_handlers())).unwrap();
+    let (addr, handle) = serve_tcp("127.0.0.1:0".parse().unwrap(), module)
+        .await
+        .unwrap();
+    let mut corpus = generate_corpus(7, 2);
+    corpus.push(RequestData {
+        function: "nope".to_string(),
+        ..corpus[0].clone()
+    });
+    let config = LoadConfig {
+        concurrency: 2,
+        requests: 3,
+        ..LoadConfig::default()
+    };
+    let report = run(&format!("http://{}", addr), &corpus, config)
+        .await
+        .unwrap();
+    assert_eq!((report.total.calls, report.total.errors), (3, 1));
+    assert_eq!(report.functions["third_root"].calls, 1);
+    assert_eq!(report.functions["nope"].error_rate(), 1.0);
+    assert_eq!(report.error_messages.values().sum::<usize>(), 1);
+    assert!(report.throughput > 0.0);
+    handle.stop().unwrap();
+}
@@ -1,107 +1,347 @@
-mod foreign_call;
-mod handlers;
-mod ops;
-
-use jsonrpsee::server::{RpcModule, Server};
-use std::net::SocketAddr;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use serde::Deserialize;
-use serde_json::{json, Value};
-
-use crate::foreign_call::ForeignCallParam;
-use crate::handlers::{
-    handle_get_pairing_witnesses, handle_is_third_root, handle_random_third_root,
-    handle_third_root, handle_witness_gen,
-};
+use rust_extension_fields::bindings;
+use rust_extension_fields::cache::{CacheConfig, OracleCache};
+use rust_extension_fields::commands;
+use rust_extension_fields::declarations;
+use rust_extension_fields::dispatch::{Dispatcher, WorkerLimits};
+use rust_extension_fields::metrics;
+use rust_extension_fields::packages::{PackageRouter, RoutingConfig};
+use rust_extension_fields::registry::OracleRegistry;
+use rust_extension_fields::replay::{Recorder, ReplayLog};
+use rust_extension_fields::server::{self, Listen};
+use rust_extension_fields::session::SessionStore;
 
-// SPIN UP THE SERVER
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let filter = tracing_subscriber::EnvFilter::try_from_default_env()?
-        .add_directive("jsonrpsee[method_call{name = \"say_hello\"}]=trace".parse()?);
-    tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(filter)
-        .finish()
-        .try_init()?;
-
-    let _server_addr = run_server().await?;
+/// Foreign call oracle of the Noir pairing library, and offline tools around its witness
+/// generation.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serve the oracles over JSON-RPC to `nargo --oracle-resolver`.
+    Serve(Box<ServeArgs>),
+    /// Compute the pairing witnesses (c, u) of an Fp12.
+    Witness(Fp12Args),
+    /// Compute a cube root of an Fp12.
+    ThirdRoot(Fp12Args),
+    /// Run sanity checks of the witness generation math.
+    Selftest {
+        /// Seed of the RNG the checked values are drawn from.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Check the `#[oracle(...)]` declarations of the Noir library against the handlers.
+    CheckOracles {
+        /// `src` directory of the Noir crate.
+        #[arg(long, default_value = "lib/src")]
+        lib_src: PathBuf,
+    },
+    /// Generate the Noir `#[oracle]` declarations and wrappers of the handlers.
+    Bindings {
+        /// Write them to this file instead of stdout, e.g. `lib/src/bn/oracles.nr`.
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Convert BigNum limb arrays, as nargo sends them, to field elements.
+    Decode {
+        /// Comma separated hex limbs, least significant first, e.g. `0x1,0x0,0x0`.
+        #[arg(required = true)]
+        limbs: Vec<String>,
+    },
 }
 
-fn print_type<T>(_: &T) {
-    println!("{:?}", std::any::type_name::<T>());
+#[derive(Debug, Args)]
+struct ServeArgs {
+    #[arg(long, default_value = "127.0.0.1")]
+    address: IpAddr,
+    #[arg(long, default_value_t = 3000)]
+    port: u16,
+    /// Serve newline delimited JSON-RPC on stdin/stdout instead of listening on a port, the
+    /// server exits once stdin is closed.
+    #[arg(long, conflicts_with_all = ["address", "port", "unix", "address_file"])]
+    stdio: bool,
+    /// Listen on a Unix domain socket at this path instead of a TCP port.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["address", "port"])]
+    unix: Option<PathBuf>,
+    /// Also write the address the server listens on to this file, e.g. with `--port 0`.
+    #[arg(long, value_name = "FILE")]
+    address_file: Option<PathBuf>,
+    /// TOML file with per-package oracle settings, see `packages::RoutingConfig`.
+    #[arg(long, value_name = "FILE", env = "ORACLE_PACKAGES")]
+    packages: Option<PathBuf>,
+    /// Reject Fp12 inputs with coefficients that aren't below the base field modulus instead of
+    /// reducing them with a warning. Packages configured with `--packages` can turn it on for
+    /// themselves, but not off.
+    #[arg(long, env = "ORACLE_STRICT")]
+    strict: bool,
+    /// Serve Prometheus metrics on `GET /metrics` at this address.
+    #[arg(long, value_name = "ADDR", env = "ORACLE_METRICS_ADDRESS")]
+    metrics_address: Option<SocketAddr>,
+    /// Tracing filter used when RUST_LOG isn't set.
+    #[arg(long, default_value = "info")]
+    log_level: String,
+    /// `json` writes one JSON object per log line, with the fields of the foreign call span.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, env = "ORACLE_LOG_FORMAT")]
+    log_format: LogFormat,
+    /// Heavy oracle calls allowed to run at once, defaults to the number of cores.
+    #[arg(long, env = "ORACLE_MAX_IN_FLIGHT")]
+    workers: Option<usize>,
+    /// Seconds a heavy oracle call may take, 0 disables the timeout.
+    #[arg(long, env = "ORACLE_CALL_TIMEOUT_SECS")]
+    call_timeout_secs: Option<u64>,
+    /// Seconds an idle session is kept around.
+    #[arg(long, env = "ORACLE_SESSION_TTL_SECS")]
+    session_ttl_secs: Option<u64>,
+    /// Oracle answers memoized in memory.
+    #[arg(long, env = "ORACLE_CACHE_ENTRIES")]
+    cache_entries: Option<usize>,
+    /// Directory the memoized answers are persisted in.
+    #[arg(long, env = "ORACLE_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Oracle answers kept in the cache directory.
+    #[arg(long, env = "ORACLE_CACHE_DISK_ENTRIES")]
+    cache_disk_entries: Option<usize>,
+    /// Append every answered call to a JSONL log.
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer calls from a log written by `--record` instead of computing them.
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize)]
-struct RequestData {
-    session_id: u64,
-    function: String,
-    inputs: Vec<ForeignCallParam<String>>,
-    root_path: String,
-    package_name: String,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
 }
 
-#[derive(Debug, Deserialize)]
-struct Requests(Vec<RequestData>); // Wrap it in a struct to handle the array
+#[derive(Debug, Args)]
+struct Fp12Args {
+    /// The 12 coefficients, c0.c0.c0 first, as hex (optionally 0x prefixed).
+    #[arg(num_args = 12, required_unless_present = "file")]
+    coefficients: Vec<String>,
+    /// Read the coefficients from a file, separated by whitespace or commas.
+    #[arg(long, conflicts_with = "coefficients")]
+    file: Option<PathBuf>,
+    /// Print the raw oracle answer (BigNum limbs) instead of the coefficients.
+    #[arg(long)]
+    limbs: bool,
+}
 
-pub(crate) fn handle_unknown_function(_input: &RequestData) -> Value {
-    println!("oops");
-    json!(vec![String::from("oops")])
+impl Fp12Args {
+    fn fq12(&self) -> anyhow::Result<ark_bn254::Fq12> {
+        match &self.file {
+            Some(path) => commands::parse_fq12(&commands::read_coefficients(path)?),
+            None => commands::parse_fq12(&self.coefficients),
+        }
+    }
 }
 
-async fn run_server() -> anyhow::Result<SocketAddr> {
-    let server = Server::builder()
-        .build("127.0.0.1:3000".parse::<SocketAddr>()?)
-        .await?;
-    let mut module = RpcModule::new(());
-
-    module.register_method("say_hello", |_, _, _| "hello, world")?;
-
-    module.register_method("resolve_foreign_call", |params, _, _| {
-        // println!("\n\nNEW REQUEST!!!");
-        // print_type(&params);
-        // println!("params{:?}", params);
-
-        let response: Value = if let Some(json_string) = params.as_str() {
-            // Deserialize the JSON string into the Requests struct:
-            let requests: Requests =
-                serde_json::from_str(&json_string).expect("Failed to parse JSON");
-
-            let request = &requests.0[0];
-
-            // println!("Request function{:?}", request.function);
-
-            let result: Value = match request.function.as_str() {
-                "witness_gen" => handle_witness_gen(&request.inputs),
-                "third_root" => handle_third_root(&request.inputs),
-                "is_third_root" => handle_is_third_root(&request.inputs),
-                "random_third_root" => handle_random_third_root(&request.inputs),
-                "get_pairing_witnesses" => handle_get_pairing_witnesses(&request.inputs),
-                _ => handle_unknown_function(&request),
-            };
-
-            result
-        } else {
-            println!("No parameters provided");
-            json!(vec![String::from("Bad query")])
-        };
+fn main() -> anyhow::Result<()> {
+    let registry = OracleRegistry::with_default_handlers();
+    match Cli::parse().command {
+        Command::Serve(args) => serve(registry, *args),
+        Command::Witness(args) => {
+            let (answer, c, u) = commands::witness(&registry, &args.fq12()?)?;
+            if args.limbs {
+                println!("{}", answer);
+            } else {
+                let coefficients = json!({
+                    "c": commands::fq12_coefficients(&c),
+                    "u": commands::fq12_coefficients(&u),
+                });
+                println!("{}", serde_json::to_string_pretty(&coefficients)?);
+            }
+            Ok(())
+        }
+        Command::ThirdRoot(args) => match commands::third_root(&registry, &args.fq12()?)? {
+            Some((answer, _)) if args.limbs => {
+                println!("{}", answer);
+                Ok(())
+            }
+            Some((_, root)) => {
+                let coefficients = commands::fq12_coefficients(&root);
+                println!("{}", serde_json::to_string_pretty(&coefficients)?);
+                Ok(())
+            }
+            None => anyhow::bail!("the input is not a cube in Fp12"),
+        },
+        Command::Selftest { seed } => {
+            let checks = commands::selftest(&registry, seed);
+            for check in &checks {
+                let outcome = if check.passed { "PASS" } else { "FAIL" };
+                println!("{} {}", outcome, check.name);
+            }
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} checks failed", failed, checks.len());
+            }
+            Ok(())
+        }
+        Command::CheckOracles { lib_src } => {
+            let findings = declarations::check(&lib_src, &registry)?;
+            for finding in &findings {
+                let kind = if finding.is_error() { "error" } else { "note" };
+                println!("{}: {}", kind, finding);
+            }
+            let errors = findings.iter().filter(|finding| finding.is_error()).count();
+            if errors > 0 {
+                anyhow::bail!("{} oracle declarations don't match the handlers", errors);
+            }
+            Ok(())
+        }
+        Command::Bindings { output } => {
+            let bindings = bindings::generate(&registry);
+            match output {
+                Some(path) => std::fs::write(path, bindings)?,
+                None => print!("{}", bindings),
+            }
+            Ok(())
+        }
+        Command::Decode { limbs } => {
+            for limbs in &limbs {
+                let value = commands::decode_limbs(limbs)?;
+                println!("{} = 0x{}", value, value.to_str_radix(16));
+            }
+            Ok(())
+        }
+    }
+}
 
-        response
-    })?;
+// SPIN UP THE SERVER
+#[tokio::main]
+async fn serve(registry: OracleRegistry, args: ServeArgs) -> anyhow::Result<()> {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .or_else(|_| tracing_subscriber::EnvFilter::try_new(&args.log_level))?;
+    // stdout carries the responses in stdio mode, so logs always go to stderr
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match args.log_format {
+        LogFormat::Text => subscriber.finish().try_init()?,
+        LogFormat::Json => subscriber
+            .json()
+            .with_span_list(false)
+            .finish()
+            .try_init()?,
+    }
 
-    let addr = server.local_addr()?;
-    let handle = server.start(module);
+    let sessions = Arc::new(match args.session_ttl_secs {
+        Some(ttl_secs) => SessionStore::new(Duration::from_secs(ttl_secs)),
+        None => SessionStore::default(),
+    });
+    let mut config = match &args.packages {
+        Some(path) => RoutingConfig::load(path)?,
+        None => RoutingConfig::default(),
+    };
+    if args.strict {
+        // the routes replace the built-in oracles only, handlers registered downstream stay,
+        // and the cache tells their answers apart from lenient ones
+        config.make_strict();
+    }
+    let router = PackageRouter::from_config(&config, &registry)?;
+    let mut dispatcher = Dispatcher::new(registry)
+        .with_router(Arc::new(router))
+        .with_limits(worker_limits(&args))
+        .with_sessions(sessions.clone())
+        .with_cache(Arc::new(OracleCache::open(cache_config(&args))?));
+    if let Some(path) = &args.record {
+        dispatcher = dispatcher.with_recorder(Arc::new(Recorder::open(path)?));
+        eprintln!("Recording oracle calls to {}", path.display());
+    }
+    if let Some(path) = &args.replay {
+        let replay = ReplayLog::load(path)?;
+        eprintln!(
+            "Replaying {} oracle calls from {}",
+            replay.len(),
+            path.display()
+        );
+        dispatcher = dispatcher.with_replay(Arc::new(replay));
+    }
 
-    println!("Server is running on 127.0.0.1:3000");
+    server::spawn_session_expiry(sessions);
+    if let Some(addr) = args.metrics_address {
+        let addr = metrics::serve_metrics(addr, dispatcher.clone()).await?;
+        eprintln!("Metrics are served on http://{}/metrics", addr);
+    }
+    let module = server::rpc_module(dispatcher)?;
+    if args.stdio {
+        let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+        return server::serve_stdio(module, stdin, tokio::io::stdout()).await;
+    }
+    let listen = match &args.unix {
+        Some(path) => Listen::Unix(path.clone()),
+        None => Listen::Tcp(SocketAddr::new(args.address, args.port)),
+    };
+    let (bound, handle) = server::serve(&listen, module).await?;
+    if let Some(path) = &args.address_file {
+        write_address_file(path, &bound)?;
+    }
 
-    // In this example we don't care about doing shutdown so let's it run forever.
-    // You may use the `ServerHandle` to shut it down or manage it yourself.
-    // tokio::spawn(handle.stopped());
+    println!("Server is running on {}", bound);
 
     // Keep the server running until it's interrupted
     handle.stopped().await;
 
-    Ok(addr)
+    Ok(())
+}
+
+// written to a temporary file first, so whoever polls for the file never reads half an address
+fn write_address_file(path: &Path, bound: &Listen) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, format!("{}\n", bound))?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn worker_limits(args: &ServeArgs) -> WorkerLimits {
+    let mut limits = WorkerLimits::default();
+    if let Some(workers) = args.workers {
+        limits.max_in_flight = workers;
+    }
+    if let Some(timeout_secs) = args.call_timeout_secs {
+        limits.call_timeout = match timeout_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+    }
+    limits
+}
+
+fn cache_config(args: &ServeArgs) -> CacheConfig {
+    let mut config = CacheConfig::default();
+    if let Some(entries) = args.cache_entries {
+        config.memory_entries = entries;
+    }
+    if let Some(entries) = args.cache_disk_entries {
+        config.disk_entries = entries;
+    }
+    config.disk_dir = args.cache_dir.clone();
+    config
+}
+
+#[test]
+fn test_cli() {
+    use clap::CommandFactory;
+    Cli::command().debug_assert();
+
+    let cli = Cli::try_parse_from(["oracle", "serve", "--port", "0", "--workers", "2"]).unwrap();
+    let Command::Serve(args) = cli.command else {
+        panic!("expected serve");
+    };
+    assert_eq!((args.port, worker_limits(&args).max_in_flight), (0, 2));
+    let conflicting = ["oracle", "serve", "--record", "a", "--replay", "b"];
+    assert!(Cli::try_parse_from(conflicting).is_err());
+    assert!(Cli::try_parse_from(["oracle", "witness", "1", "2"]).is_err());
 }
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::dispatch::Dispatcher;
+
+/// Upper bounds, in seconds, of the call latency histogram buckets. Oracle calls range from
+/// microseconds (`witness_gen`) to minutes (a cold `get_pairing_witnesses`).
+pub const LATENCY_BUCKETS: [f64; 14] = [
+    0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+];
+
+/// Call counters and latency histograms per oracle, filled in by the [`Dispatcher`] as it
+/// resolves calls.
+#[derive(Default)]
+pub struct Metrics {
+    functions: Mutex<BTreeMap<String, FunctionMetrics>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionMetrics {
+    pub calls: u64,
+    pub errors: u64,
+    /// Calls answered from the cache.
+    pub cache_hits: u64,
+    /// Calls per latency bucket, the last one counts the calls above every bound.
+    pub buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    pub duration_sum: Duration,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&self, function: &str, ok: bool, cached: bool, took: Duration) {
+        let mut functions = self.functions.lock().unwrap();
+        let metrics = functions.entry(function.to_string()).or_default();
+        metrics.calls += 1;
+        if !ok {
+            metrics.errors += 1;
+        }
+        if cached {
+            metrics.cache_hits += 1;
+        }
+        let seconds = took.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        metrics.buckets[bucket] += 1;
+        metrics.duration_sum += took;
+    }
+
+    pub fn function(&self, function: &str) -> Option<FunctionMetrics> {
+        self.functions.lock().unwrap().get(function).cloned()
+    }
+}
+
+// reads one per function counter
+type Counter = fn(&FunctionMetrics) -> u64;
+
+/// The metrics of `dispatcher` in the Prometheus text exposition format.
+pub fn render(dispatcher: &Dispatcher) -> String {
+    let functions = dispatcher.metrics().functions.lock().unwrap().clone();
+    let mut out = String::new();
+
+    let counters: [(&str, &str, Counter); 3] = [
+        ("oracle_calls_total", "Foreign calls resolved.", |m| m.calls),
+        (
+            "oracle_call_errors_total",
+            "Foreign calls answered with an error.",
+            |m| m.errors,
+        ),
+        (
+            "oracle_cache_hits_total",
+            "Foreign calls answered from the cache.",
+            |m| m.cache_hits,
+        ),
+    ];
+    for (name, help, value) in counters {
+        header(&mut out, name, "counter", help);
+        for (function, metrics) in &functions {
+            sample(&mut out, name, function, "", value(metrics));
+        }
+    }
+
+    let name = "oracle_call_duration_seconds";
+    header(
+        &mut out,
+        name,
+        "histogram",
+        "Time spent resolving a foreign call.",
+    );
+    for (function, metrics) in &functions {
+        let bucket = format!("{}_bucket", name);
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&metrics.buckets) {
+            cumulative += count;
+            let le = format!(",le=\"{}\"", bound);
+            sample(&mut out, &bucket, function, &le, cumulative);
+        }
+        sample(&mut out, &bucket, function, ",le=\"+Inf\"", metrics.calls);
+        let sum = metrics.duration_sum.as_secs_f64();
+        sample(&mut out, &format!("{}_sum", name), function, "", sum);
+        let count = format!("{}_count", name);
+        sample(&mut out, &count, function, "", metrics.calls);
+    }
+
+    let mut gauges = vec![
+        (
+            "oracle_in_flight",
+            "Blocking computations running.",
+            dispatcher.in_flight(),
+        ),
+        (
+            "oracle_max_in_flight",
+            "Blocking computations allowed at once.",
+            dispatcher.limits().max_in_flight,
+        ),
+        (
+            "oracle_sessions",
+            "Live nargo sessions.",
+            dispatcher.sessions().len(),
+        ),
+    ];
+    if let Some(cache) = dispatcher.cache() {
+        let name = "oracle_cache_lookups_total";
+        header(&mut out, name, "counter", "Cache lookups by result.");
+        let _ = writeln!(out, "{}{{result=\"hit\"}} {}", name, cache.hits());
+        let _ = writeln!(out, "{}{{result=\"miss\"}} {}", name, cache.misses());
+        gauges.push((
+            "oracle_cache_entries",
+            "Answers held in the memory cache.",
+            cache.memory_len(),
+        ));
+    }
+    for (name, help, value) in gauges {
+        header(&mut out, name, "gauge", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// one sample of a per function metric, `labels` are appended after the function label
+fn sample(out: &mut String, name: &str, function: &str, labels: &str, value: impl Display) {
+    let function = function
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    let _ = writeln!(
+        out,
+        "{}{{function=\"{}\"{}}} {}",
+        name, function, labels, value
+    );
+}
+
+/// How long a metrics client may take to send its request headers before it is disconnected.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request head (request line and headers) answered, a scrape is a few hundred bytes.
+pub const MAX_HEADER_BYTES: usize = 8 * 1024;
+
+/// Serves [`render`] on `GET /metrics` at `addr`, returning the bound address.
+///
+/// This is a bare HTTP/1.1 responder meant for a local scraper, one request per connection.
+/// Connections that don't send their headers within [`READ_TIMEOUT`] are closed, and heads
+/// over [`MAX_HEADER_BYTES`] are refused.
+pub async fn serve_metrics(addr: SocketAddr, dispatcher: Dispatcher) -> anyhow::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!("failed to accept a metrics connection: {}", err);
+                    continue;
+                }
+            };
+            let dispatcher = dispatcher.clone();
+            tokio::spawn(async move {
+                if let Err(err) = respond(stream, &dispatcher, READ_TIMEOUT).await {
+                    tracing::debug!("metrics connection closed: {}", err);
+                }
+            });
+        }
+    });
+    Ok(addr)
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    dispatcher: &Dispatcher,
+    read_timeout: Duration,
+) -> std::io::Result<()> {
+    let head = tokio::time::timeout(read_timeout, read_head(&mut stream))
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("no request headers within {:?}", read_timeout),
+            )
+        })??;
+    let (status, body) = match head {
+        Some(request) => {
+            let request = String::from_utf8_lossy(&request);
+            let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+            match (request_line.next(), request_line.next()) {
+                (Some("GET"), Some("/metrics")) => ("200 OK", render(dispatcher)),
+                (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+                _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+            }
+        }
+        None => (
+            "431 Request Header Fields Too Large",
+            "request header fields too large\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// the request up to the end of its headers (or of the stream), `None` once it outgrows
+// MAX_HEADER_BYTES. Only the request line matters.
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > MAX_HEADER_BYTES {
+            return Ok(None);
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    Ok(Some(request))
+}
+
+#[test]
+fn test_histogram_buckets() {
+    let metrics = Metrics::new();
+    metrics.observe("third_root", true, false, Duration::from_micros(500));
+    metrics.observe("third_root", false, false, Duration::from_secs(2));
+    metrics.observe("third_root", true, true, Duration::from_secs(1000));
+    let third_root = metrics.function("third_root").unwrap();
+    assert_eq!(
+        (third_root.calls, third_root.errors, third_root.cache_hits),
+        (3, 1, 1)
+    );
+    assert_eq!(third_root.buckets[0], 1);
+    assert_eq!(third_root.buckets[7], 1);
+    assert_eq!(third_root.buckets[LATENCY_BUCKETS.len()], 1);
+    assert!(metrics.function("is_third_root").is_none());
+}
+
+#[tokio::test]
+async fn test_serve_metrics() {
+    use crate::registry::OracleRegistry;
+    use crate::request::Requests;
+
+    let dispatcher = Dispatcher::new(OracleRegistry::with_default_handlers());
+    let request = crate::request::test_request("witness_gen");
+    dispatcher
+        .resolve_requests_async(&Requests(vec![request]))
+        .await
+        .unwrap();
+    let addr = serve_metrics("127.0.0.1:0".parse().unwrap(), dispatcher)
+        .await
+        .unwrap();
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("oracle_calls_total{function=\"witness_gen\"} 1\n"));
+    assert!(response
+        .contains("oracle_call_duration_seconds_bucket{function=\"witness_gen\",le=\"+Inf\"} 1\n"));
+    assert!(response.contains("oracle_in_flight 0\n"));
+}
+
+#[tokio::test]
+async fn test_metrics_connections_are_bounded() {
+    use crate::registry::OracleRegistry;
+
+    let dispatcher = Dispatcher::new(OracleRegistry::with_default_handlers());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // an idle client is dropped once the read timeout passes
+    let _idle = TcpStream::connect(addr).await.unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let err = respond(stream, &dispatcher, Duration::from_millis(10))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    // a client that never ends its headers is refused
+    let mut client = TcpStream::connect(addr).await.unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let (responded, _) = tokio::join!(
+        respond(stream, &dispatcher, READ_TIMEOUT),
+        client.write_all(&[b'a'; MAX_HEADER_BYTES + 1])
+    );
+    responded.unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+}
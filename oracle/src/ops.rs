@@ -1,20 +1,21 @@
-use ark_ff::{BigInteger768, BitIteratorBE, Field, PrimeField}; // Add BitIteratorBE to the imports
-                                                               // We'll use a field associated with the BLS12-381 pairing-friendly
-                                                               // group for this example.
+use ark_ff::{Field, PrimeField};
 use ark_bn254::Fr;
 use ark_bn254::{Fq, Fq12};
-use ark_test_curves::bls12_381::Fq2 as F;
 // use ark_grumpkin::Fq as Fr;
 // use ark_test_curves::bn254::Fq12 as F2;
 // `ark-std` is a utility crate that enables `arkworks` libraries
 // to easily support `std` and `no_std` workloads, and also re-exports
 // useful crates that should be common across the entire ecosystem, such as `rand`.
+use ark_std::rand::Rng;
 use ark_std::{One, UniformRand};
 use num_bigint::BigUint;
 
 use ark_ff::BigInt;
 use num_bigint::BigInt as BI;
 
+/// Bump whenever a change to the witness generation changes what the oracles answer, this
+/// invalidates every cached result computed by the previous version.
+pub const WITNESS_ALGORITHM_VERSION: u32 = 1;
 
 pub struct WitnessGenerator {
 }
@@ -33,9 +34,11 @@ pub trait WitnessGeneratorTrait {
     fn invert(a: &BigUint, modulus: &BigUint) -> BI;
     fn extended_gcd(_a: &BI, _b: &BI) -> (BI, BI, BI);
     fn rand_third_root() -> Fq12;
+    fn rand_third_root_from<R: Rng>(rng: &mut R) -> Fq12;
 }
 
 impl WitnessGeneratorTrait for WitnessGenerator {
+    #[allow(clippy::assign_op_pattern)]
     fn witness_generator(input: Fq12) -> (Fq12, Fq12) {
     let w = Self::find_27th_root();   
     let mut f = input;
@@ -57,6 +60,7 @@ impl WitnessGeneratorTrait for WitnessGenerator {
     (c, w.pow([3^s as u64]))
 }
 
+ #[allow(clippy::assign_op_pattern, clippy::let_and_return)]
  fn mp_th_root_of_c(c: Fq12) -> Fq12 {
     // m = lambda / r
     // lambda = 2 + q - q^2 + q^3 + 6*x
@@ -83,6 +87,7 @@ impl WitnessGeneratorTrait for WitnessGenerator {
     res
 }
 
+ #[allow(clippy::let_and_return)]
  fn r_th_root_of_f(f: Fq12) -> Fq12 {
     // the value of r is
     //21888242871839275222246405745257275088548364400416034343698204186575808495617
@@ -124,6 +129,7 @@ impl WitnessGeneratorTrait for WitnessGenerator {
     (b.clone(), x.clone(), y.clone())
 }
 
+ #[allow(clippy::assign_op_pattern)]
  fn invert(a: &BigUint, modulus: &BigUint) -> BI {
     // perform a -> BI conversion
     let a_bigint = BI::from(a.clone());
@@ -145,12 +151,18 @@ impl WitnessGeneratorTrait for WitnessGenerator {
     let exp_biguint: BigUint = (s + BigUint::one()) / BigUint::from(3u64);
     let exp: BigInt<50> = BigInt::<50>::try_from(exp_biguint).unwrap();
     let mut x = a.pow(exp);
-    while x.pow([3]) * (a.inverse().unwrap()) != Fq12::one() {
-        x = x * w;
+    // w generates the 27th roots of unity, so if a is a cube one of x * w^i with i < 27 is a
+    // cube root of it. A non-cube would otherwise loop forever.
+    for _ in 0..27 {
+        if x.pow([3]) * (a.inverse().unwrap()) == Fq12::one() {
+            return x;
+        }
+        x *= w;
     }
-    x
+    panic!("tonelli_shanks_third_root: input is not a cube");
 }
 
+ #[allow(clippy::needless_borrows_for_generic_args, clippy::needless_return)]
  fn get_order(a: Fq12) -> u32 {
     let mut a = a;
     let mut t = 0;
@@ -185,6 +197,7 @@ impl WitnessGeneratorTrait for WitnessGenerator {
     }
 }
 
+ #[allow(clippy::let_and_return)]
  fn pow_p12_minus_one_div_27(a: &Fq12) -> Fq12 {
     let p = Fq::MODULUS;
     let p_biguint: BigUint = p.into();
@@ -196,6 +209,7 @@ impl WitnessGeneratorTrait for WitnessGenerator {
     res
 }
 
+ #[allow(clippy::let_and_return)]
  fn pow_p12_minus_one_div_3(a: &Fq12) -> Fq12 {
     let p = Fq::MODULUS;
     let p_biguint: BigUint = p.into();
@@ -214,9 +228,13 @@ fn is_third_root(a: &Fq12) -> bool {
 
 fn rand_third_root() -> Fq12 {
     let mut rng = ark_std::test_rng();
-    let mut a = Fq12::rand(&mut rng);
+    Self::rand_third_root_from(&mut rng)
+}
+
+fn rand_third_root_from<R: Rng>(rng: &mut R) -> Fq12 {
+    let mut a = Fq12::rand(rng);
     while !Self::is_third_root(&a) {
-        a = Fq12::rand(&mut rng);
+        a = Fq12::rand(rng);
     }
     a
 }
@@ -268,7 +286,7 @@ fn test_get_order() {
     let mut rng = ark_std::test_rng();
     let a = Fq12::rand(&mut rng);
     let b = WitnessGenerator::pow_p12_minus_one_div_27(&a);
-    let res = WitnessGenerator::get_order(b);
+    WitnessGenerator::get_order(b);
 }
 
 #[test]
@@ -288,7 +306,6 @@ fn test_pow_fq12() {
 #[test]
 fn test_find_third_non_residue() {
     let res = WitnessGenerator::find_third_non_residue();
-    let a = res.0;
     let b = res.1;
     let c = b.pow([3]);
     assert_ne!(c, Fq12::one());
@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::registry::OracleRegistry;
+use crate::request::RequestData;
+
+/// Curve whose field arithmetic a package's oracles use. The Noir library only declares oracles
+/// for bn254 (`lib/src/bn/oracles.nr`), the bls12_381 pairing computes its witnesses in Noir.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    #[default]
+    Bn254,
+}
+
+impl Curve {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Curve::Bn254 => "bn254",
+        }
+    }
+
+    /// The oracles served for this curve: the oracles of `base` (which may hold extra handlers,
+    /// and may already be strict), made strict when `strict` is set.
+    pub fn registry(&self, base: &OracleRegistry, strict: bool) -> OracleRegistry {
+        let mut registry = base.clone();
+        if strict {
+            crate::handlers::register_handlers(&mut registry, true);
+        }
+        registry
+    }
+}
+
+/// How the oracle serves the calls of one Noir package.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageSettings {
+    pub curve: Curve,
+    /// Reject non-canonical inputs instead of reducing them.
+    pub strict: bool,
+    /// Cache entries are shared between packages with the same namespace, which defaults to the
+    /// curve name.
+    pub cache_namespace: Option<String>,
+    /// The oracles the package may call, every oracle of the curve when `None`.
+    pub handlers: Option<Vec<String>>,
+}
+
+impl PackageSettings {
+    pub fn cache_namespace(&self) -> &str {
+        self.cache_namespace
+            .as_deref()
+            .unwrap_or_else(|| self.curve.name())
+    }
+}
+
+/// A `[[package]]` entry of the routing config: which calls it matches, and the
+/// [`PackageSettings`] they are served with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackageRule {
+    /// The `package_name` nargo sends, i.e. the `name` in the package's Nargo.toml.
+    pub name: String,
+    /// Only match calls whose `root_path` lies under this directory.
+    #[serde(default)]
+    pub root_path: Option<PathBuf>,
+    // spelled out rather than a flattened `PackageSettings`, serde can't deny unknown fields
+    // through `flatten`
+    #[serde(default)]
+    pub curve: Curve,
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(default)]
+    pub cache_namespace: Option<String>,
+    #[serde(default)]
+    pub handlers: Option<Vec<String>>,
+}
+
+impl PackageRule {
+    pub fn settings(&self) -> PackageSettings {
+        PackageSettings {
+            curve: self.curve,
+            strict: self.strict,
+            cache_namespace: self.cache_namespace.clone(),
+            handlers: self.handlers.clone(),
+        }
+    }
+
+    fn matches(&self, package_name: &str, root_path: &str) -> bool {
+        self.name == package_name
+            && self
+                .root_path
+                .as_ref()
+                .is_none_or(|root| Path::new(root_path).starts_with(root))
+    }
+}
+
+/// Per-package oracle settings, read from a TOML file such as
+///
+/// ```toml
+/// [default]
+/// curve = "bn254"
+///
+/// [[package]]
+/// name = "example"
+/// strict = true
+/// cache_namespace = "example"
+/// handlers = ["get_pairing_witnesses"]
+/// ```
+///
+/// A call is served by the first `[[package]]` matching its `package_name` (and `root_path`,
+/// if the rule has one), or by `[default]` otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingConfig {
+    #[serde(default)]
+    pub default: PackageSettings,
+    #[serde(default, rename = "package")]
+    pub packages: Vec<PackageRule>,
+}
+
+impl RoutingConfig {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_toml(&contents).map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))
+    }
+
+    pub fn from_toml(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Turns strict mode on for the default and every package, as `serve --strict` does.
+    pub fn make_strict(&mut self) {
+        self.default.strict = true;
+        for rule in &mut self.packages {
+            rule.strict = true;
+        }
+    }
+}
+
+/// The oracles and settings calls of a package are served with.
+pub struct Route {
+    /// Name of the `[[package]]` rule, `None` for the default route.
+    pub package: Option<String>,
+    pub settings: PackageSettings,
+    pub registry: OracleRegistry,
+}
+
+impl Route {
+    /// A route serving `registry` with default settings.
+    pub fn new(registry: OracleRegistry) -> Self {
+        Route {
+            package: None,
+            settings: PackageSettings::default(),
+            registry,
+        }
+    }
+
+    fn from_settings(
+        package: Option<String>,
+        settings: PackageSettings,
+        base: &OracleRegistry,
+    ) -> anyhow::Result<Self> {
+        let mut registry = settings.curve.registry(base, settings.strict);
+        if let Some(handlers) = &settings.handlers {
+            let mut selected = OracleRegistry::new();
+            for name in handlers {
+                let oracle = registry.get(name).ok_or_else(|| {
+                    anyhow::anyhow!("{} has no {} oracle", settings.curve.name(), name)
+                })?;
+                selected.register_oracle(oracle.clone());
+            }
+            registry = selected;
+        }
+        Ok(Route {
+            package,
+            settings,
+            registry,
+        })
+    }
+}
+
+/// Picks the [`Route`] of a call from the `package_name` and `root_path` nargo sends with it.
+pub struct PackageRouter {
+    default: Route,
+    packages: Vec<(PackageRule, Route)>,
+}
+
+impl PackageRouter {
+    /// A router sending every call to `default`.
+    pub fn new(default: Route) -> Self {
+        PackageRouter {
+            default,
+            packages: vec![],
+        }
+    }
+
+    /// Routes calls as `config` says, every package's oracles are taken from `base`.
+    pub fn from_config(config: &RoutingConfig, base: &OracleRegistry) -> anyhow::Result<Self> {
+        let mut router = Self::new(Route::from_settings(None, config.default.clone(), base)?);
+        for rule in &config.packages {
+            let route = Route::from_settings(Some(rule.name.clone()), rule.settings(), base)
+                .map_err(|err| anyhow::anyhow!("package {}: {}", rule.name, err))?;
+            router.packages.push((rule.clone(), route));
+        }
+        Ok(router)
+    }
+
+    pub fn default_route(&self) -> &Route {
+        &self.default
+    }
+
+    pub fn route(&self, request: &RequestData) -> &Route {
+        self.packages
+            .iter()
+            .find(|(rule, _)| rule.matches(&request.package_name, &request.root_path))
+            .map_or(&self.default, |(_, route)| route)
+    }
+}
+
+#[cfg(test)]
+fn request(package_name: &str, root_path: &str) -> RequestData {
+    RequestData {
+        root_path: root_path.to_string(),
+        package_name: package_name.to_string(),
+        ..crate::request::test_request("third_root")
+    }
+}
+
+#[test]
+fn test_route_by_package_and_root_path() {
+    let config = RoutingConfig::from_toml(
+        r#"
+        [default]
+        handlers = ["witness_gen"]
+
+        [[package]]
+        name = "example"
+        root_path = "/work/other"
+        cache_namespace = "other"
+
+        [[package]]
+        name = "example"
+        strict = true
+        handlers = ["third_root", "is_third_root"]
+        "#,
+    )
+    .unwrap();
+    let router =
+        PackageRouter::from_config(&config, &OracleRegistry::with_default_handlers()).unwrap();
+
+    let other = router.route(&request("example", "/work/other/example"));
+    assert_eq!(other.settings.cache_namespace(), "other");
+    assert_eq!(other.registry.list().len(), 5);
+
+    let example = router.route(&request("example", "/work/noir_pairing/example"));
+    assert!(example.settings.strict);
+    assert_eq!(example.settings.cache_namespace(), "bn254");
+    assert!(example.registry.get("third_root").is_some());
+    assert!(example.registry.get("witness_gen").is_none());
+
+    let default = router.route(&request("pairing", "/work/other"));
+    assert_eq!(default.package, None);
+    assert_eq!(default.registry.list().len(), 1);
+}
+
+#[test]
+fn test_config_errors() {
+    let unknown_field = RoutingConfig::from_toml("[default]\nstrictness = true\n");
+    assert!(unknown_field.is_err());
+    let unknown_field = RoutingConfig::from_toml("[[package]]\nname = \"x\"\nstrictt = true\n");
+    assert!(unknown_field.is_err());
+    // there are no bls12_381 oracles to serve
+    let bls = RoutingConfig::from_toml("[[package]]\nname = \"bls\"\ncurve = \"bls12_381\"\n");
+    assert!(bls.is_err());
+    let missing = RoutingConfig::from_toml("[default]\nhandlers = [\"nope\"]\n");
+    let base = OracleRegistry::with_default_handlers();
+    assert!(PackageRouter::from_config(&missing.unwrap(), &base).is_err());
+}
+
+#[test]
+fn test_routes_keep_the_base_registry() {
+    use crate::handlers::IsThirdRoot;
+    use crate::registry::Oracle;
+    use crate::session::Session;
+
+    // a handler registered downstream, and the default strict mode
+    let mut base = OracleRegistry::new();
+    crate::handlers::register_handlers(&mut base, true);
+    base.register(Extra);
+    let config = RoutingConfig::from_toml(
+        "[[package]]\nname = \"lenient\"\n\n[[package]]\nname = \"extra\"\nhandlers = [\"extra\"]\n",
+    )
+    .unwrap();
+    let router = PackageRouter::from_config(&config, &base).unwrap();
+    let extra = router.route(&request("extra", ""));
+    assert!(extra.registry.get("extra").is_some());
+    assert_eq!(extra.registry.list().len(), 1);
+    assert!(router.default_route().registry.get("extra").is_some());
+
+    // p + 1 isn't canonical, the strict base rejects it even though the package doesn't ask
+    let mut inputs = crate::commands::fp12_inputs(&ark_std::One::one());
+    inputs[0] = crate::request::non_canonical_fq(1);
+    let lenient = router.route(&request("lenient", ""));
+    let session = Session::new(0, "", "");
+    assert!(lenient
+        .registry
+        .get("is_third_root")
+        .unwrap()
+        .call(&inputs, &session)
+        .is_err());
+    assert!(IsThirdRoot { strict: false }
+        .call(&inputs, &session)
+        .is_ok());
+}
+
+#[cfg(test)]
+struct Extra;
+
+#[cfg(test)]
+impl crate::registry::OracleHandler for Extra {
+    type Input = ();
+    type Output = ();
+
+    fn name(&self) -> &'static str {
+        "extra"
+    }
+
+    fn input_shape(&self) -> Vec<crate::registry::ParamShape> {
+        vec![]
+    }
+
+    fn output_shape(&self) -> Vec<crate::registry::ParamShape> {
+        vec![]
+    }
+
+    fn decode(
+        &self,
+        _inputs: &[crate::foreign_call::ForeignCallParam<String>],
+    ) -> Result<(), crate::error::OracleError> {
+        Ok(())
+    }
+
+    fn compute(
+        &self,
+        _input: (),
+        _session: &crate::session::Session,
+    ) -> Result<(), crate::error::OracleError> {
+        Ok(())
+    }
+
+    fn encode(&self, _output: ()) -> Result<serde_json::Value, crate::error::OracleError> {
+        Ok(serde_json::json!({ "values": [] }))
+    }
+}
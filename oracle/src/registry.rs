@@ -0,0 +1,329 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::bindings::NoirSignature;
+use crate::error::OracleError;
+use crate::foreign_call::ForeignCallParam;
+use crate::session::Session;
+
+/// Layout of a single foreign call parameter as nargo sends it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamShape {
+    /// A single field value (`Field`, `bool`, `u32`, ...).
+    Single,
+    /// An array of field values, e.g. the limbs of a `BigNum`.
+    Array(usize),
+}
+
+impl std::fmt::Display for ParamShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParamShape::Single => write!(f, "single"),
+            ParamShape::Array(len) => write!(f, "array({})", len),
+        }
+    }
+}
+
+impl ParamShape {
+    pub fn of<F>(param: &ForeignCallParam<F>) -> ParamShape {
+        match param {
+            ForeignCallParam::Single(_) => ParamShape::Single,
+            ForeignCallParam::Array(values) => ParamShape::Array(values.len()),
+        }
+    }
+}
+
+/// Checks that `inputs` are laid out as `expected`: as many params, each a single value or an
+/// array of the declared length.
+pub fn check_shape<F>(
+    expected: &[ParamShape],
+    inputs: &[ForeignCallParam<F>],
+) -> Result<(), OracleError> {
+    if inputs.len() != expected.len() {
+        return Err(OracleError::WrongArity {
+            expected: expected.len(),
+            got: inputs.len(),
+        });
+    }
+    for (index, (expected, input)) in expected.iter().zip(inputs).enumerate() {
+        let got = ParamShape::of(input);
+        if got != *expected {
+            return Err(OracleError::WrongShape {
+                index,
+                expected: *expected,
+                got,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// A foreign function the oracle can resolve.
+///
+/// Implementors only write the typed part of an oracle: how to decode the foreign call inputs,
+/// what to compute and how to encode the result for the Noir program. The registry takes care
+/// of dispatching calls by name.
+pub trait OracleHandler: Send + Sync {
+    type Input;
+    type Output;
+
+    /// The name used in the `#[oracle(...)]` attribute on the Noir side.
+    fn name(&self) -> &'static str;
+
+    /// The parameters the Noir declaration passes, in order. Calls are checked against it before
+    /// they are decoded.
+    fn input_shape(&self) -> Vec<ParamShape>;
+
+    /// The values the Noir declaration returns, in order.
+    fn output_shape(&self) -> Vec<ParamShape>;
+
+    /// The Noir types of the declaration, from which its bindings are generated. `None` for
+    /// oracles that aren't part of the Noir library.
+    fn noir_signature(&self) -> Option<NoirSignature> {
+        None
+    }
+
+    fn decode(&self, inputs: &[ForeignCallParam<String>]) -> Result<Self::Input, OracleError>;
+
+    /// `session` holds the state of the nargo run the call belongs to.
+    fn compute(&self, input: Self::Input, session: &Session) -> Result<Self::Output, OracleError>;
+
+    fn encode(&self, output: Self::Output) -> Result<Value, OracleError>;
+
+    /// Whether a call is expensive enough that it has to run on the blocking worker pool
+    /// instead of on the RPC thread.
+    fn is_blocking(&self) -> bool {
+        false
+    }
+
+    /// Whether the answer only depends on the inputs, so it may be served from the cache.
+    fn is_cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// Object safe view of an [`OracleHandler`], so handlers with different input and output types
+/// can live in the same registry.
+pub trait Oracle: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn input_shape(&self) -> Vec<ParamShape>;
+    fn output_shape(&self) -> Vec<ParamShape>;
+    fn noir_signature(&self) -> Option<NoirSignature>;
+    fn is_blocking(&self) -> bool;
+    fn is_cacheable(&self) -> bool;
+    fn call(
+        &self,
+        inputs: &[ForeignCallParam<String>],
+        session: &Session,
+    ) -> Result<Value, OracleError>;
+}
+
+impl<H: OracleHandler> Oracle for H {
+    fn name(&self) -> &'static str {
+        OracleHandler::name(self)
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        OracleHandler::input_shape(self)
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        OracleHandler::output_shape(self)
+    }
+
+    fn noir_signature(&self) -> Option<NoirSignature> {
+        OracleHandler::noir_signature(self)
+    }
+
+    fn is_blocking(&self) -> bool {
+        OracleHandler::is_blocking(self)
+    }
+
+    fn is_cacheable(&self) -> bool {
+        OracleHandler::is_cacheable(self)
+    }
+
+    fn call(
+        &self,
+        inputs: &[ForeignCallParam<String>],
+        session: &Session,
+    ) -> Result<Value, OracleError> {
+        // the steps' durations land on the caller's span (see `Dispatcher`), if there is one
+        let span = tracing::Span::current();
+        let started = Instant::now();
+        check_shape(&OracleHandler::input_shape(self), inputs)?;
+        let input = self.decode(inputs)?;
+        span.record("decode_us", started.elapsed().as_micros() as u64);
+        let started = Instant::now();
+        let output = self.compute(input, session)?;
+        span.record("compute_us", started.elapsed().as_micros() as u64);
+        let started = Instant::now();
+        let encoded = self.encode(output)?;
+        span.record("encode_us", started.elapsed().as_micros() as u64);
+        Ok(encoded)
+    }
+}
+
+/// What the registry knows about a registered oracle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OracleInfo {
+    pub name: String,
+    pub inputs: Vec<ParamShape>,
+    pub outputs: Vec<ParamShape>,
+}
+
+/// The set of oracles a server resolves foreign calls against, keyed by function name.
+#[derive(Clone, Default)]
+pub struct OracleRegistry {
+    oracles: BTreeMap<String, Arc<dyn Oracle>>,
+}
+
+impl OracleRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding the oracles declared by the Noir library in `lib/`.
+    pub fn with_default_handlers() -> Self {
+        let mut registry = Self::new();
+        crate::handlers::register_default_handlers(&mut registry);
+        registry
+    }
+
+    /// Registers `handler` under its name, returning the oracle it replaced (if any).
+    pub fn register<H: OracleHandler + 'static>(&mut self, handler: H) -> Option<Arc<dyn Oracle>> {
+        self.register_oracle(Arc::new(handler))
+    }
+
+    pub fn register_oracle(&mut self, oracle: Arc<dyn Oracle>) -> Option<Arc<dyn Oracle>> {
+        self.oracles.insert(oracle.name().to_string(), oracle)
+    }
+
+    pub fn get(&self, function: &str) -> Option<&Arc<dyn Oracle>> {
+        self.oracles.get(function)
+    }
+
+    /// All registered oracles, sorted by name.
+    pub fn list(&self) -> Vec<OracleInfo> {
+        self.oracles
+            .values()
+            .map(|oracle| OracleInfo {
+                name: oracle.name().to_string(),
+                inputs: oracle.input_shape(),
+                outputs: oracle.output_shape(),
+            })
+            .collect()
+    }
+
+    pub fn call(
+        &self,
+        function: &str,
+        inputs: &[ForeignCallParam<String>],
+        session: &Session,
+    ) -> Result<Value, OracleError> {
+        match self.get(function) {
+            Some(oracle) => oracle.call(inputs, session),
+            None => Err(OracleError::UnknownFunction(function.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+struct Echo;
+
+#[cfg(test)]
+impl OracleHandler for Echo {
+    type Input = Vec<String>;
+    type Output = Vec<String>;
+
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn input_shape(&self) -> Vec<ParamShape> {
+        vec![ParamShape::Array(2)]
+    }
+
+    fn output_shape(&self) -> Vec<ParamShape> {
+        vec![ParamShape::Array(2)]
+    }
+
+    fn decode(&self, inputs: &[ForeignCallParam<String>]) -> Result<Self::Input, OracleError> {
+        Ok(inputs[0].get_values())
+    }
+
+    fn compute(&self, input: Self::Input, _session: &Session) -> Result<Self::Output, OracleError> {
+        Ok(input.into_iter().rev().collect())
+    }
+
+    fn encode(&self, output: Self::Output) -> Result<Value, OracleError> {
+        Ok(serde_json::json!({ "values": [output] }))
+    }
+}
+
+#[test]
+fn test_register_and_call() {
+    let mut registry = OracleRegistry::new();
+    let session = Session::new(0, "", "");
+    assert!(registry.register(Echo).is_none());
+    let inputs = vec![ForeignCallParam::Array(vec![
+        "1".to_string(),
+        "2".to_string(),
+    ])];
+    assert_eq!(
+        registry.call("echo", &inputs, &session).unwrap(),
+        serde_json::json!({ "values": [["2", "1"]] })
+    );
+    assert_eq!(
+        registry.call("nope", &inputs, &session),
+        Err(OracleError::UnknownFunction("nope".to_string()))
+    );
+}
+
+#[test]
+fn test_inputs_are_checked_against_the_declared_shape() {
+    let mut registry = OracleRegistry::new();
+    let session = Session::new(0, "", "");
+    registry.register(Echo);
+    let call = |inputs: Vec<ForeignCallParam<String>>| registry.call("echo", &inputs, &session);
+    let one = || "1".to_string();
+    assert_eq!(
+        call(vec![]),
+        Err(OracleError::WrongArity {
+            expected: 1,
+            got: 0
+        })
+    );
+    assert_eq!(
+        call(vec![ForeignCallParam::Single(one())]),
+        Err(OracleError::WrongShape {
+            index: 0,
+            expected: ParamShape::Array(2),
+            got: ParamShape::Single
+        })
+    );
+    let err = call(vec![ForeignCallParam::Array(vec![one(), one(), one()])]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "wrong shape: expected input 0 to be array(2), got array(3)"
+    );
+}
+
+#[test]
+fn test_list_and_replace() {
+    let mut registry = OracleRegistry::new();
+    registry.register(Echo);
+    assert!(registry.register(Echo).is_some());
+    assert_eq!(
+        registry.list(),
+        vec![OracleInfo {
+            name: "echo".to_string(),
+            inputs: vec![ParamShape::Array(2)],
+            outputs: vec![ParamShape::Array(2)],
+        }]
+    );
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::OracleError;
+use crate::foreign_call::ForeignCallParam;
+
+/// One line of a record log: a resolved foreign call and what the oracle answered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    /// The Noir package that made the call, logs written before it was recorded leave it empty.
+    #[serde(default)]
+    pub package_name: String,
+    pub function: String,
+    pub inputs: Vec<ForeignCallParam<String>>,
+    pub outputs: Value,
+    /// Wall clock time the oracle spent on the call, in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// Appends every successfully resolved call to a JSONL log.
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, call: &RecordedCall) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(call)?;
+        line.push('\n');
+        // one write per line so concurrent calls never interleave inside an entry
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()
+    }
+}
+
+/// Answers foreign calls from a record log instead of computing them.
+///
+/// Calls are matched on package, function name and exact inputs, so packages served by
+/// different handlers (see [`crate::packages`]) never get each other's answers. When the same call was recorded several
+/// times (e.g. `random_third_root`), the answers are handed out in recorded order, starting over
+/// once they run out.
+pub struct ReplayLog {
+    answers: Mutex<HashMap<String, (Vec<Value>, usize)>>,
+}
+
+impl ReplayLog {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let mut calls = vec![];
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let call: RecordedCall = serde_json::from_str(&line).map_err(|err| {
+                anyhow::anyhow!("{}:{}: {}", path.display(), line_number + 1, err)
+            })?;
+            calls.push(call);
+        }
+        Ok(Self::from_calls(calls))
+    }
+
+    pub fn from_calls(calls: impl IntoIterator<Item = RecordedCall>) -> Self {
+        let mut answers: HashMap<String, (Vec<Value>, usize)> = HashMap::new();
+        for call in calls {
+            answers
+                .entry(replay_key(&call.package_name, &call.function, &call.inputs))
+                .or_default()
+                .0
+                .push(call.outputs);
+        }
+        ReplayLog {
+            answers: Mutex::new(answers),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.answers
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(a, _)| a.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn lookup(
+        &self,
+        package_name: &str,
+        function: &str,
+        inputs: &[ForeignCallParam<String>],
+    ) -> Result<Value, OracleError> {
+        let mut answers = self.answers.lock().unwrap();
+        match answers.get_mut(&replay_key(package_name, function, inputs)) {
+            Some((outputs, next)) => {
+                let output = outputs[*next % outputs.len()].clone();
+                *next += 1;
+                Ok(output)
+            }
+            None => Err(OracleError::NotRecorded(format!(
+                "no recorded answer for {} of package {:?} with inputs {}",
+                function,
+                package_name,
+                serde_json::to_string(inputs).unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+fn replay_key(package_name: &str, function: &str, inputs: &[ForeignCallParam<String>]) -> String {
+    // serializing a package name and a list of strings can't fail, and the JSON quoting keeps
+    // a `:` in the package name from running into the function name
+    format!(
+        "{}:{}:{}",
+        serde_json::to_string(package_name).unwrap(),
+        function,
+        serde_json::to_string(inputs).unwrap()
+    )
+}
+
+#[cfg(test)]
+fn recorded(function: &str, input: &str, output: &str) -> RecordedCall {
+    RecordedCall {
+        package_name: String::new(),
+        function: function.to_string(),
+        inputs: vec![ForeignCallParam::Single(input.to_string())],
+        outputs: serde_json::json!({ "values": [output] }),
+        duration_ms: 1,
+    }
+}
+
+#[test]
+fn test_replay_answers_in_recorded_order() {
+    let log = ReplayLog::from_calls(vec![
+        recorded("random", "0", "a"),
+        recorded("other", "0", "x"),
+        recorded("random", "0", "b"),
+    ]);
+    let inputs = vec![ForeignCallParam::Single("0".to_string())];
+    let mut answers = vec![];
+    for _ in 0..3 {
+        answers.push(log.lookup("", "random", &inputs).unwrap()["values"][0].clone());
+    }
+    assert_eq!(answers, vec!["a", "b", "a"]);
+    assert_eq!(log.lookup("", "other", &inputs).unwrap()["values"][0], "x");
+}
+
+#[test]
+fn test_replay_fails_on_unrecorded_call() {
+    let log = ReplayLog::from_calls(vec![recorded("random", "0", "a")]);
+    let inputs = vec![ForeignCallParam::Single("1".to_string())];
+    assert!(matches!(
+        log.lookup("", "random", &inputs),
+        Err(OracleError::NotRecorded(_))
+    ));
+}
+
+#[test]
+fn test_record_then_load() {
+    let path = std::env::temp_dir().join(format!("oracle-record-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let recorder = Recorder::open(&path).unwrap();
+    recorder.record(&recorded("third_root", "1", "2")).unwrap();
+    recorder.record(&recorded("third_root", "3", "4")).unwrap();
+    let log = ReplayLog::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(log.len(), 2);
+    let inputs = vec![ForeignCallParam::Single("3".to_string())];
+    assert_eq!(
+        log.lookup("", "third_root", &inputs).unwrap()["values"][0],
+        "4"
+    );
+}
+
+#[test]
+fn test_replay_keeps_packages_apart() {
+    let other_package = RecordedCall {
+        package_name: "bls".to_string(),
+        ..recorded("third_root", "1", "b")
+    };
+    let log = ReplayLog::from_calls(vec![recorded("third_root", "1", "a"), other_package]);
+    let inputs = vec![ForeignCallParam::Single("1".to_string())];
+    assert_eq!(
+        log.lookup("", "third_root", &inputs).unwrap()["values"][0],
+        "a"
+    );
+    assert_eq!(
+        log.lookup("bls", "third_root", &inputs).unwrap()["values"][0],
+        "b"
+    );
+    assert!(matches!(
+        log.lookup("bn", "third_root", &inputs),
+        Err(OracleError::NotRecorded(_))
+    ));
+    // logs recorded before packages were part of the key replay for the default package
+    let old: RecordedCall = serde_json::from_str(
+        r#"{"function":"f","inputs":[],"outputs":{"values":[]},"duration_ms":0}"#,
+    )
+    .unwrap();
+    assert_eq!(old.package_name, "");
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::OracleError;
+use crate::foreign_call::ForeignCallParam;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestData {
+    pub session_id: u64,
+    pub function: String,
+    pub inputs: Vec<ForeignCallParam<String>>,
+    // nargo 0.36 leaves these out when it doesn't know them
+    #[serde(default)]
+    pub root_path: String,
+    #[serde(default)]
+    pub package_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Requests(pub Vec<RequestData>); // Wrap it in a struct to handle the array
+
+impl Requests {
+    // maps the raw `params` of a resolve_foreign_call call onto the requests it carries. We accept
+    // - a JSON encoded string holding any of the shapes below (what older clients send)
+    // - a single request object
+    // - a positional array of request objects (what nargo sends), possibly stringified per entry
+    pub fn from_params(raw_params: &str) -> Result<Requests, OracleError> {
+        let params: Value = serde_json::from_str(raw_params).map_err(parse_error)?;
+        request_values(params)?
+            .into_iter()
+            .map(|entry| serde_json::from_value(entry).map_err(parse_error))
+            .collect::<Result<Vec<RequestData>, OracleError>>()
+            .map(Requests)
+    }
+}
+
+// the request objects of the params, unwrapping stringified ones
+fn request_values(params: Value) -> Result<Vec<Value>, OracleError> {
+    match params {
+        Value::String(json_string) => {
+            request_values(serde_json::from_str(&json_string).map_err(parse_error)?)
+        }
+        Value::Object(_) => Ok(vec![params]),
+        Value::Array(entries) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                Value::String(json_string) => {
+                    serde_json::from_str(&json_string).map_err(parse_error)
+                }
+                entry => Ok(entry),
+            })
+            .collect(),
+        other => Err(OracleError::Parse(format!(
+            "expected a request object, array or string, got {}",
+            other
+        ))),
+    }
+}
+
+fn parse_error(err: serde_json::Error) -> OracleError {
+    OracleError::Parse(err.to_string())
+}
+
+/// A request for `function` from session 0 of an unnamed package, tests adjust the other
+/// fields with struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_request(function: &str) -> RequestData {
+    RequestData {
+        session_id: 0,
+        function: function.to_string(),
+        inputs: vec![],
+        root_path: String::new(),
+        package_name: String::new(),
+    }
+}
+
+/// `p + by` as the limbs of a BigNum<3, 254>: a base field input that isn't canonical and that
+/// lenient handlers reduce to `by`.
+#[cfg(test)]
+pub(crate) fn non_canonical_fq(by: u32) -> ForeignCallParam<String> {
+    use crate::limbs::{LimbCodec, LIMB_BITS};
+    let modulus = LimbCodec::bn254_fq().modulus().clone();
+    ForeignCallParam::Array(
+        LimbCodec::unbounded(LIMB_BITS, 3)
+            .encode(&(modulus + by))
+            .unwrap(),
+    )
+}
+
+#[cfg(test)]
+const REQUEST_JSON: &str = r#"{"session_id":7,"function":"third_root","inputs":[["1","0","0"]],"root_path":"/tmp","package_name":"pairing"}"#;
+
+#[test]
+fn test_from_params_stringified() {
+    let raw = serde_json::to_string(&format!("[{}]", REQUEST_JSON)).unwrap();
+    let requests = Requests::from_params(&raw).unwrap();
+    assert_eq!(requests.0.len(), 1);
+    assert_eq!(requests.0[0].function, "third_root");
+}
+
+#[test]
+fn test_from_params_object() {
+    let requests = Requests::from_params(REQUEST_JSON).unwrap();
+    assert_eq!(requests.0.len(), 1);
+    assert_eq!(requests.0[0].session_id, 7);
+    assert_eq!(
+        requests.0[0].inputs,
+        vec![ForeignCallParam::Array(vec![
+            "1".to_string(),
+            "0".to_string(),
+            "0".to_string()
+        ])]
+    );
+}
+
+#[test]
+fn test_from_params_array() {
+    let stringified_entry = serde_json::to_string(REQUEST_JSON).unwrap();
+    let raw = format!("[{}, {}]", REQUEST_JSON, stringified_entry);
+    let requests = Requests::from_params(&raw).unwrap();
+    assert_eq!(requests.0.len(), 2);
+    assert_eq!(requests.0[0].package_name, "pairing");
+    assert_eq!(requests.0[1].package_name, "pairing");
+    // nargo leaves out the root path and package name it doesn't know
+    let requests =
+        Requests::from_params(r#"{"session_id":1,"function":"third_root","inputs":[]}"#).unwrap();
+    assert_eq!(requests.0[0].root_path, "");
+    assert_eq!(requests.0[0].package_name, "");
+}
+
+#[test]
+fn test_from_params_rejects_other_shapes() {
+    assert!(matches!(
+        Requests::from_params("42"),
+        Err(OracleError::Parse(_))
+    ));
+    assert!(matches!(
+        Requests::from_params("{not json"),
+        Err(OracleError::Parse(_))
+    ));
+    assert!(matches!(
+        Requests::from_params(r#"[{"function":"third_root"}]"#),
+        Err(OracleError::Parse(_))
+    ));
+}
@@ -0,0 +1,316 @@
+use jsonrpsee::server::{stop_channel, Methods, RpcModule, Server, ServerHandle};
+use jsonrpsee::types::{ErrorCode, ErrorObject, ErrorObjectOwned};
+use serde_json::{json, Value};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UnixListener;
+
+use crate::dispatch::Dispatcher;
+use crate::error::OracleError;
+use crate::info;
+use crate::request::Requests;
+use crate::session::SessionStore;
+
+/// The JSON-RPC methods the oracle serves, on top of `dispatcher`.
+pub fn rpc_module(dispatcher: Dispatcher) -> anyhow::Result<RpcModule<Dispatcher>> {
+    let mut module = RpcModule::new(dispatcher);
+
+    module.register_method("say_hello", |_, _, _| "hello, world")?;
+    module.register_method("health", |_, dispatcher, _| json!(info::health(dispatcher)))?;
+    module.register_method("version", |_, _, _| json!(info::version_info()))?;
+    module.register_method("list_oracles", |_, dispatcher, _| {
+        dispatcher.registry().list()
+    })?;
+
+    // lets a test harness free a session's state as soon as its nargo run is done
+    module.register_method("end_session", |params, dispatcher, _| {
+        let session_id: u64 = params.one()?;
+        Ok::<bool, ErrorObjectOwned>(dispatcher.sessions().end(session_id))
+    })?;
+
+    // heavy oracles are moved onto the blocking worker pool by the dispatcher, so the RPC
+    // workers stay free to accept calls from parallel nargo runs
+    module.register_async_method("resolve_foreign_call", |params, dispatcher, _| async move {
+        let raw_params = params
+            .as_str()
+            .ok_or_else(|| OracleError::Parse("no parameters provided".to_string()))?;
+        resolve_foreign_call(&dispatcher, raw_params).await
+    })?;
+
+    Ok(module)
+}
+
+/// Decodes the raw `params` of a `resolve_foreign_call` call (string, object or array) and
+/// resolves the requests they carry.
+pub async fn resolve_foreign_call(
+    dispatcher: &Dispatcher,
+    raw_params: &str,
+) -> Result<Value, ErrorObjectOwned> {
+    let requests = Requests::from_params(raw_params)?;
+    dispatcher
+        .resolve_requests_async(&requests)
+        .await
+        .map_err(ErrorObjectOwned::from)
+}
+
+/// Where the HTTP and WebSocket server listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    /// A TCP address, port 0 lets the OS pick a free port.
+    Tcp(SocketAddr),
+    /// A Unix domain socket path.
+    Unix(PathBuf),
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listen::Tcp(addr) => write!(f, "{}", addr),
+            Listen::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Serves `module` on `listen`, returning where it actually listens (the OS assigned port of
+/// an ephemeral TCP address).
+pub async fn serve(
+    listen: &Listen,
+    module: RpcModule<Dispatcher>,
+) -> anyhow::Result<(Listen, ServerHandle)> {
+    match listen {
+        Listen::Tcp(addr) => {
+            let (addr, handle) = serve_tcp(*addr, module).await?;
+            Ok((Listen::Tcp(addr), handle))
+        }
+        Listen::Unix(path) => Ok((listen.clone(), serve_unix(path, module)?)),
+    }
+}
+
+/// Serves `module` over HTTP and WebSocket on `addr`.
+pub async fn serve_tcp(
+    addr: SocketAddr,
+    module: RpcModule<Dispatcher>,
+) -> anyhow::Result<(SocketAddr, ServerHandle)> {
+    let server = Server::builder().build(addr).await?;
+    let addr = server.local_addr()?;
+    Ok((addr, server.start(module)))
+}
+
+/// Serves `module` over HTTP and WebSocket on the Unix domain socket at `path`.
+///
+/// A socket left behind by a previous run is replaced, and the socket is removed again once
+/// the server is stopped.
+pub fn serve_unix(path: &Path, module: RpcModule<Dispatcher>) -> anyhow::Result<ServerHandle> {
+    remove_stale_socket(path)?;
+    let listener = UnixListener::bind(path)?;
+    let path = path.to_path_buf();
+    let methods: Methods = module.into();
+    let service_builder = Server::builder().to_service_builder();
+    let (stop_handle, server_handle) = stop_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        tracing::warn!("failed to accept a connection: {}", err);
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+            let service = service_builder
+                .clone()
+                .build(methods.clone(), stop_handle.clone());
+            let stopped = stop_handle.clone().shutdown();
+            tokio::spawn(async move {
+                let served =
+                    jsonrpsee::server::serve_with_graceful_shutdown(stream, service, stopped);
+                if let Err(err) = served.await {
+                    tracing::debug!("connection closed: {}", err);
+                }
+            });
+        }
+        let _ = std::fs::remove_file(&path);
+    });
+    Ok(server_handle)
+}
+
+fn remove_stale_socket(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        Ok(_) => anyhow::bail!("{} exists and is not a socket", path.display()),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Serves `module` over newline delimited JSON-RPC: one request (or batch) per line of `input`,
+/// one response per line of `output`, until `input` is closed.
+///
+/// Lets a wrapper script or test harness own the oracle process without opening a port.
+pub async fn serve_stdio<R, W>(
+    module: RpcModule<Dispatcher>,
+    input: R,
+    mut output: W,
+) -> anyhow::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = respond(&module, &line).await;
+        output.write_all(response.as_bytes()).await?;
+        output.write_all(b"\n").await?;
+        output.flush().await?;
+    }
+    Ok(())
+}
+
+async fn respond(module: &RpcModule<Dispatcher>, line: &str) -> String {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Array(batch)) if !batch.is_empty() => {
+            let mut responses = vec![];
+            for request in batch {
+                responses.push(respond_one(module, &request.to_string()).await);
+            }
+            format!("[{}]", responses.join(","))
+        }
+        Ok(Value::Array(_)) => error_response(ErrorCode::InvalidRequest),
+        Ok(_) => respond_one(module, line).await,
+        Err(_) => error_response(ErrorCode::ParseError),
+    }
+}
+
+async fn respond_one(module: &RpcModule<Dispatcher>, request: &str) -> String {
+    match module.raw_json_request(request, 1).await {
+        Ok((response, _)) => response,
+        Err(_) => error_response(ErrorCode::InvalidRequest),
+    }
+}
+
+// a request we couldn't read has no id to answer to
+fn error_response(code: ErrorCode) -> String {
+    json!({ "jsonrpc": "2.0", "id": null, "error": ErrorObject::from(code) }).to_string()
+}
+
+/// Drops the sessions of nargo runs that stopped calling us, checking once per ttl.
+pub fn spawn_session_expiry(sessions: Arc<SessionStore>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(sessions.ttl().max(Duration::from_secs(1)));
+        loop {
+            interval.tick().await;
+            sessions.expire_idle();
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_serve_tcp_resolves_foreign_calls() {
+    use crate::registry::OracleRegistry;
+    use jsonrpsee::core::client::ClientT;
+    use jsonrpsee::http_client::HttpClientBuilder;
+    use jsonrpsee::rpc_params;
+
+    let module = rpc_module(Dispatcher::new(OracleRegistry::with_default_handlers())).unwrap();
+    let (addr, handle) = serve_tcp("127.0.0.1:0".parse().unwrap(), module)
+        .await
+        .unwrap();
+    let client = HttpClientBuilder::default()
+        .build(format!("http://{}", addr))
+        .unwrap();
+    let request = json!({
+        "session_id": 3,
+        "function": "witness_gen",
+        "inputs": [],
+        "root_path": "",
+        "package_name": "",
+    });
+    let answer: Value = client
+        .request("resolve_foreign_call", rpc_params![request])
+        .await
+        .unwrap();
+    assert_eq!(answer, json!("Hello, world!"));
+    let version: Value = client.request("version", rpc_params![]).await.unwrap();
+    assert_eq!(version["crate_version"], env!("CARGO_PKG_VERSION"));
+    handle.stop().unwrap();
+}
+
+#[tokio::test]
+async fn test_serve_stdio() {
+    use crate::registry::OracleRegistry;
+
+    let module = rpc_module(Dispatcher::new(OracleRegistry::with_default_handlers())).unwrap();
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "resolve_foreign_call",
+        "params": [{
+            "session_id": 3,
+            "function": "witness_gen",
+            "inputs": [],
+            "root_path": "",
+            "package_name": "",
+        }],
+    });
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": 2, "method": "say_hello" },
+        { "jsonrpc": "2.0", "id": 3, "method": "nope" },
+    ]);
+    let input = format!("{}\n\n{}\nnot json\n", request, batch);
+    let mut output = vec![];
+    serve_stdio(module, input.as_bytes(), &mut output)
+        .await
+        .unwrap();
+
+    let responses: Vec<Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["id"], 1);
+    assert_eq!(responses[0]["result"], "Hello, world!");
+    assert_eq!(responses[1][0]["result"], "hello, world");
+    assert_eq!(responses[1][1]["error"]["code"], -32601);
+    assert_eq!(responses[2]["error"]["code"], -32700);
+}
+
+#[tokio::test]
+async fn test_serve_unix() {
+    use crate::registry::OracleRegistry;
+    use tokio::io::AsyncReadExt;
+
+    let path = std::env::temp_dir().join(format!("oracle-{}.sock", std::process::id()));
+    let module = rpc_module(Dispatcher::new(OracleRegistry::with_default_handlers())).unwrap();
+    let (bound, handle) = serve(&Listen::Unix(path.clone()), module).await.unwrap();
+    assert_eq!(bound.to_string(), format!("unix:{}", path.display()));
+
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": "say_hello" }).to_string();
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let request = format!(
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with(r#""result":"hello, world"}"#));
+
+    handle.stop().unwrap();
+    handle.stopped().await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!path.exists());
+}
@@ -0,0 +1,207 @@
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::request::RequestData;
+
+/// State the oracle keeps for one nargo run, identified by the `session_id` nargo sends along
+/// with every foreign call.
+pub struct Session {
+    id: u64,
+    root_path: String,
+    package_name: String,
+    created_at: Instant,
+    last_used: Mutex<Instant>,
+    calls: AtomicU64,
+    rng: Mutex<StdRng>,
+    data: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl Session {
+    pub fn new(id: u64, root_path: &str, package_name: &str) -> Self {
+        let now = Instant::now();
+        Session {
+            id,
+            root_path: root_path.to_string(),
+            package_name: package_name.to_string(),
+            created_at: now,
+            last_used: Mutex::new(now),
+            calls: AtomicU64::new(0),
+            // seeded from the session id so a rerun with the same id sees the same randomness
+            rng: Mutex::new(StdRng::seed_from_u64(id)),
+            data: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn root_path(&self) -> &str {
+        &self.root_path
+    }
+
+    pub fn package_name(&self) -> &str {
+        &self.package_name
+    }
+
+    pub fn age(&self) -> Duration {
+        self.created_at.elapsed()
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_used.lock().unwrap().elapsed()
+    }
+
+    /// Number of foreign calls resolved in this session so far.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    fn record_call(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    /// Runs `f` with the session's seeded RNG.
+    pub fn with_rng<R>(&self, f: impl FnOnce(&mut StdRng) -> R) -> R {
+        f(&mut self.rng.lock().unwrap())
+    }
+
+    /// Runs `f` with the session's value of type `T` (accumulated values, caches, ...), which is
+    /// created with `T::default()` on first use. Every type gets its own slot.
+    pub fn with_data<T, R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Default + Send + 'static,
+    {
+        let mut data = self.data.lock().unwrap();
+        let slot = data
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()));
+        f(slot.downcast_mut::<T>().expect("session data slot holds its own type"))
+    }
+}
+
+/// All live sessions of a server. Sessions are created on the first call that carries their id
+/// and dropped once they have been idle for longer than the store's time to live.
+pub struct SessionStore {
+    sessions: Mutex<HashMap<u64, Arc<Session>>>,
+    ttl: Duration,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30 * 60))
+    }
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        SessionStore {
+            sessions: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The session `request` belongs to, counting the request as a call made in it.
+    pub fn session_for(&self, request: &RequestData) -> Arc<Session> {
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .entry(request.session_id)
+            .or_insert_with(|| {
+                Arc::new(Session::new(
+                    request.session_id,
+                    &request.root_path,
+                    &request.package_name,
+                ))
+            })
+            .clone();
+        session.record_call();
+        session
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Ends a session explicitly, returning whether it existed.
+    pub fn end(&self, id: u64) -> bool {
+        self.sessions.lock().unwrap().remove(&id).is_some()
+    }
+
+    /// Drops every session that has been idle for longer than the ttl, returning how many.
+    pub fn expire_idle(&self) -> usize {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.idle_for() <= self.ttl);
+        before - sessions.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+fn request(session_id: u64) -> RequestData {
+    RequestData {
+        session_id,
+        root_path: "/tmp".to_string(),
+        package_name: "pairing".to_string(),
+        ..crate::request::test_request("witness_gen")
+    }
+}
+
+#[test]
+fn test_sessions_are_isolated() {
+    let store = SessionStore::default();
+    let a = store.session_for(&request(1));
+    let b = store.session_for(&request(2));
+    a.with_data(|counter: &mut u32| *counter += 5);
+    b.with_data(|counter: &mut u32| *counter += 1);
+    assert_eq!(store.session_for(&request(1)).with_data(|c: &mut u32| *c), 5);
+    assert_eq!(b.with_data(|c: &mut u32| *c), 1);
+    assert_eq!(a.calls(), 2);
+    assert_eq!(b.calls(), 1);
+    assert_eq!(a.package_name(), "pairing");
+}
+
+#[test]
+fn test_session_rng_is_seeded_by_id() {
+    use ark_std::rand::RngCore;
+    let first = SessionStore::default().session_for(&request(7));
+    let second = SessionStore::default().session_for(&request(7));
+    let other = SessionStore::default().session_for(&request(8));
+    let draw = |session: &Session| session.with_rng(|rng| rng.next_u64());
+    let value = draw(&first);
+    assert_eq!(value, draw(&second));
+    assert_ne!(value, draw(&other));
+    // the rng advances within a session
+    assert_ne!(draw(&first), value);
+}
+
+#[test]
+fn test_expire_and_end_sessions() {
+    let store = SessionStore::new(Duration::ZERO);
+    store.session_for(&request(1));
+    store.session_for(&request(2));
+    assert!(store.end(2));
+    assert!(!store.end(2));
+    std::thread::sleep(Duration::from_millis(5));
+    assert_eq!(store.expire_idle(), 1);
+    assert!(store.is_empty());
+}